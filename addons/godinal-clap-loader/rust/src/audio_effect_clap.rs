@@ -0,0 +1,39 @@
+use crate::{
+    audio_effect_clap_instance::AudioEffectClapInstance, clap_plugin_instance::ClapPluginInstance,
+};
+use godot::{
+    classes::{AudioEffect, AudioEffectInstance, IAudioEffect},
+    prelude::*,
+};
+
+/// 插件一次处理的最大秒数，决定了插件激活时的最大帧数。Godot混音一次通常只会处理几十毫秒的音频。
+const MAX_PROCESS_SECONDS: f64 = 0.1;
+
+/// 将Clap插件作为Godot音频总线上的效果器使用。
+///
+/// 插件直接在Godot的混音线程上处理总线的音频，帧数和采样率都与总线一致，所以不会引入额外的延迟，也不会和Godot的音频时钟产生漂移。
+/// 总线的音频会送入插件的主输入端口（第一个输入端口），插件主输出端口（第一个输出端口）的输出会作为总线的输出。
+///
+/// 每条使用本效果器的总线都会拥有一个独立的插件实例，可以通过`AudioServer.get_bus_effect_instance`取得[`AudioEffectClapInstance`]，
+/// 再通过其`get_plugin_instance`访问插件的参数、GUI、事件等。
+#[derive(GodotClass)]
+#[class(init, base = AudioEffect)]
+pub struct AudioEffectClap {
+    /// clap插件文件系统路径，也可以是`res://`之类的Godot路径。
+    #[export(global_file = "*.clap")]
+    path: GString,
+    /// 需要加载的插件编号，例如`com.u-he.diva`，可以由`ClapPluginCatalog`扫描得到。
+    #[export]
+    plugin_id: GString,
+}
+#[godot_api]
+impl IAudioEffect for AudioEffectClap {
+    fn instantiate(&mut self) -> Option<Gd<AudioEffectInstance>> {
+        let (plugin_instance, shared_audio_processor) = ClapPluginInstance::new_externally_driven(
+            &self.path,
+            &self.plugin_id,
+            MAX_PROCESS_SECONDS,
+        )?;
+        Some(AudioEffectClapInstance::new(plugin_instance, shared_audio_processor).upcast())
+    }
+}
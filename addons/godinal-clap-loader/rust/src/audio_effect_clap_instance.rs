@@ -0,0 +1,66 @@
+use crate::{clap_plugin_instance::ClapPluginInstance, host::SharedAudioProcessor};
+use godot::{
+    classes::{AudioEffectInstance, IAudioEffectInstance, native::AudioFrame},
+    prelude::*,
+};
+use std::{ffi::c_void, slice};
+
+/// [`AudioEffectClap`](crate::audio_effect_clap::AudioEffectClap)在某条音频总线上的实例，持有一个独立的Clap插件实例。
+#[derive(GodotClass)]
+#[class(no_init, base = AudioEffectInstance)]
+pub struct AudioEffectClapInstance {
+    /// 插件实例在Godot端的代理，用于在主线程上访问插件的参数、GUI、事件等。
+    plugin_instance: Gd<ClapPluginInstance>,
+    /// 与插件实例共享的音频处理器，在Godot的混音线程上使用。
+    shared_audio_processor: SharedAudioProcessor,
+}
+#[godot_api]
+impl AudioEffectClapInstance {
+    pub fn new(
+        plugin_instance: Gd<ClapPluginInstance>,
+        shared_audio_processor: SharedAudioProcessor,
+    ) -> Gd<Self> {
+        Gd::from_object(Self {
+            plugin_instance,
+            shared_audio_processor,
+        })
+    }
+
+    /// 本效果器实例所使用的插件实例，可以用于访问插件的参数、GUI、事件等。
+    #[func]
+    fn get_plugin_instance(&self) -> Gd<ClapPluginInstance> {
+        self.plugin_instance.clone()
+    }
+}
+#[godot_api]
+impl IAudioEffectInstance for AudioEffectClapInstance {
+    unsafe fn process_rawptr(
+        &mut self,
+        src_buffer: *const c_void,
+        dst_buffer: *mut AudioFrame,
+        frame_count: i32,
+    ) {
+        let frames_count = frame_count.max(0) as usize;
+        // SAFETY: Godot保证输入和输出缓冲都包含`frame_count`帧。
+        let (input, output) = unsafe {
+            (
+                slice::from_raw_parts(src_buffer as *const AudioFrame, frames_count),
+                slice::from_raw_parts_mut(dst_buffer, frames_count),
+            )
+        };
+        self.shared_audio_processor.process(input, output);
+    }
+
+    /// 总线静音时也需要处理，以便混响、延迟之类的插件输出尾音。
+    fn process_silence(&self) -> bool {
+        true
+    }
+}
+impl Drop for AudioEffectClapInstance {
+    fn drop(&mut self) {
+        if self.plugin_instance.is_instance_valid() {
+            // 效果器实例可能在混音线程上被释放，插件实例节点则只能在主线程上释放。
+            self.plugin_instance.call_deferred("queue_free", &[]);
+        }
+    }
+}
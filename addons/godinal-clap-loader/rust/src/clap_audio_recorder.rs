@@ -0,0 +1,212 @@
+use crate::wav::{WavSampleFormat, WavWriter};
+use godot::{classes::ProjectSettings, prelude::*};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter},
+    sync::mpsc::{Receiver, Sender, channel},
+    thread::{JoinHandle, spawn},
+};
+
+/// Clap插件输出端口的录音机，由[`ClapOutputAudioPortAccess::create_recorder`](crate::clap_output_audio_port_access::ClapOutputAudioPortAccess::create_recorder)创建。
+///
+/// 录制端口的所有通道，不限于作为[`IAudioStream`](godot::classes::IAudioStream)使用时的两个声道。
+/// 音频由后台线程逐块写入WAV文件，不会阻塞主线程。
+/// 录音机只在被引用期间接收音频，释放录音机会结束正在进行的录制。
+#[derive(GodotClass)]
+#[class(no_init, base = RefCounted)]
+pub struct ClapAudioRecorder {
+    /// 预录时长（秒）：没有在录制时也会保留最近这段时长的音频，开始录制时一并写入文件开头，以免错过开始录制前的声音。
+    #[var]
+    pre_roll_seconds: f64,
+    sample_rate: f64,
+    /// 没有在录制时保留的最近的音频，每个通道一个队列。
+    pre_roll_buffer: Box<[VecDeque<f32>]>,
+    /// 正在进行的录制，没有在录制时为空。
+    recording: Option<Recording>,
+    is_paused: bool,
+}
+#[godot_api]
+impl ClapAudioRecorder {
+    pub fn new(channels_count: usize, sample_rate: f64) -> Gd<Self> {
+        Gd::from_object(Self {
+            pre_roll_seconds: 0.0,
+            sample_rate,
+            pre_roll_buffer: (0..channels_count).map(|_| VecDeque::new()).collect(),
+            recording: None,
+            is_paused: false,
+        })
+    }
+
+    /// 接收端口各个通道输出的音频，每个通道的音频可能由多段组成。
+    pub fn push(&mut self, buffers: &[Box<[Box<[f32]>]>]) {
+        if self.is_paused {
+            return;
+        }
+        match &self.recording {
+            Some(recording) => {
+                let buffers = buffers
+                    .iter()
+                    .map(|buffer| buffer.concat().into())
+                    .collect();
+                // 发送失败说明后台线程已经因为写入错误退出，错误会在停止录制时报告。
+                let _ = recording.buffers_tx.send(buffers);
+            }
+            None => {
+                let pre_roll_frames_count = (self.pre_roll_seconds * self.sample_rate) as usize;
+                for (pre_roll_channel, buffer) in self.pre_roll_buffer.iter_mut().zip(buffers) {
+                    pre_roll_channel.extend(buffer.iter().flatten());
+                    let overflow = pre_roll_channel.len().saturating_sub(pre_roll_frames_count);
+                    pre_roll_channel.drain(..overflow);
+                }
+            }
+        }
+    }
+
+    /// 开始录制，已经在录制时会先停止之前的录制。预录的音频会被写入文件开头。
+    /// - `path`: 保存的路径，可以使用`res://`、`user://`等Godot路径。
+    /// - `bits_per_sample`: 每个采样的位数，`16`、`24`为整数，`32`为浮点数。
+    ///
+    /// 返回：是否成功开始录制。
+    #[func]
+    fn start(&mut self, path: GString, bits_per_sample: i64) -> bool {
+        self.stop();
+        let Some(sample_format) = WavSampleFormat::from_bits_per_sample(bits_per_sample) else {
+            godot_error!("开始录制失败，不支持{bits_per_sample}位的采样，只支持16、24、32位");
+            return false;
+        };
+
+        let path = ProjectSettings::singleton()
+            .globalize_path(&path)
+            .to_string();
+        let wav_writer = match File::create(&path).and_then(|file| {
+            WavWriter::new(
+                BufWriter::new(file),
+                sample_format,
+                self.pre_roll_buffer.len() as u16,
+                self.sample_rate.round() as u32,
+            )
+        }) {
+            Ok(wav_writer) => wav_writer,
+            Err(err) => {
+                godot_error!("开始录制失败，无法创建WAV文件“{path}”：{err}");
+                return false;
+            }
+        };
+
+        let (buffers_tx, buffers_rx) = channel();
+        let pre_roll_buffers = self
+            .pre_roll_buffer
+            .iter_mut()
+            .map(|pre_roll_channel| pre_roll_channel.drain(..).collect())
+            .collect();
+        let _ = buffers_tx.send(pre_roll_buffers);
+        let channels_count = self.pre_roll_buffer.len();
+        self.recording = Some(Recording {
+            path,
+            buffers_tx,
+            writer_thread: spawn(move || write_recording(wav_writer, channels_count, buffers_rx)),
+        });
+        self.is_paused = false;
+        true
+    }
+
+    /// 停止录制，等待后台线程把剩余的音频写入文件。没有在录制时什么都不做。
+    ///
+    /// 返回：录制的文件是否完整地保存了下来，没有在录制时为`false`。
+    #[func]
+    fn stop(&mut self) -> bool {
+        self.is_paused = false;
+        let Some(Recording {
+            path,
+            buffers_tx,
+            writer_thread,
+        }) = self.recording.take()
+        else {
+            return false;
+        };
+        // 关闭通道，后台线程写完剩余的音频后就会退出。
+        drop(buffers_tx);
+        match writer_thread.join() {
+            Ok(Ok(())) => true,
+            Ok(Err(err)) => {
+                godot_error!("保存录制的WAV文件“{path}”失败：{err}");
+                false
+            }
+            Err(_) => {
+                godot_error!("保存录制的WAV文件“{path}”失败，写入线程意外退出");
+                false
+            }
+        }
+    }
+
+    /// 暂停录制，暂停期间的音频既不会被录制，也不会被预录。
+    #[func]
+    fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    /// 从暂停中恢复，之后的音频会紧接着暂停前的音频写入同一个文件。
+    #[func]
+    fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    /// 是否正在录制，暂停期间也算作正在录制。
+    #[func]
+    fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    #[func]
+    fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+}
+impl Drop for ClapAudioRecorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 一次正在进行的录制。
+struct Recording {
+    /// 全局化之后的文件路径，用于报告错误。
+    path: String,
+    /// 向后台线程发送音频，每个通道一段。
+    buffers_tx: Sender<Box<[Box<[f32]>]>>,
+    writer_thread: JoinHandle<io::Result<()>>,
+}
+
+/// 后台线程：把收到的音频写入WAV文件，直到通道被关闭。
+///
+/// 各通道的音频不一定同时送达，只写入所有通道都已收到的帧，剩下的等到结束时补齐静音后写入。
+fn write_recording(
+    mut wav_writer: WavWriter<BufWriter<File>>,
+    channels_count: usize,
+    buffers_rx: Receiver<Box<[Box<[f32]>]>>,
+) -> io::Result<()> {
+    let mut pending_channels = vec![Vec::new(); channels_count];
+    for buffers in buffers_rx {
+        for (pending_channel, buffer) in pending_channels.iter_mut().zip(buffers) {
+            pending_channel.extend_from_slice(&buffer);
+        }
+        let frames_count = pending_channels.iter().map(Vec::len).min().unwrap_or(0);
+        let channels = pending_channels
+            .iter()
+            .map(|pending_channel| &pending_channel[..frames_count])
+            .collect::<Vec<_>>();
+        wav_writer.write_channels(&channels)?;
+        for pending_channel in &mut pending_channels {
+            pending_channel.drain(..frames_count);
+        }
+    }
+
+    let channels = pending_channels
+        .iter()
+        .map(Vec::as_slice)
+        .collect::<Vec<_>>();
+    wav_writer.write_channels(&channels)?;
+    wav_writer.finish()?;
+    Ok(())
+}
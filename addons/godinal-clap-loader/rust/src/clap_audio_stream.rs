@@ -0,0 +1,46 @@
+use crate::{
+    clap_audio_stream_playback::ClapAudioStreamPlayback, clap_plugin_instance::ClapPluginInstance,
+};
+use godot::{
+    classes::{AudioStream, AudioStreamPlayback, IAudioStream},
+    prelude::*,
+};
+
+/// Godot混音一次最多拉取的秒数，决定了插件激活时的最大帧数。
+const MAX_MIX_SECONDS: f64 = 0.1;
+
+/// 由Clap插件实时渲染的音频流，适合乐器类插件。
+///
+/// 播放时由Godot的混音线程按需拉取：Godot需要多少帧，插件就同步渲染多少帧。
+/// 所以不需要像`ClapPluginInstance`的`new_from_clap_file`那样权衡`max_latency_seconds`，`_process`卡顿时也不会断音。
+///
+/// 每次播放都会加载一个独立的插件实例，可以通过`AudioStreamPlayer.get_stream_playback`取得[`ClapAudioStreamPlayback`]，
+/// 再通过其`get_plugin_instance`向插件发送MIDI、修改参数等。
+/// 播放的是插件主输出端口（第一个输出端口）的音频。
+#[derive(GodotClass)]
+#[class(init, base = AudioStream)]
+pub struct ClapAudioStream {
+    /// clap插件文件系统路径，也可以是`res://`之类的Godot路径。
+    #[export(global_file = "*.clap")]
+    path: GString,
+    /// 需要加载的插件编号，例如`com.u-he.diva`，可以由`ClapPluginCatalog`扫描得到。
+    #[export]
+    plugin_id: GString,
+}
+#[godot_api]
+impl IAudioStream for ClapAudioStream {
+    fn instantiate_playback(&self) -> Option<Gd<AudioStreamPlayback>> {
+        let (plugin_instance, shared_audio_processor) = ClapPluginInstance::new_externally_driven(
+            &self.path,
+            &self.plugin_id,
+            MAX_MIX_SECONDS,
+        )?;
+        Some(ClapAudioStreamPlayback::new(plugin_instance, shared_audio_processor).upcast())
+    }
+    fn get_stream_name(&self) -> GString {
+        self.plugin_id.clone()
+    }
+    fn get_length(&self) -> f64 {
+        0.0
+    }
+}
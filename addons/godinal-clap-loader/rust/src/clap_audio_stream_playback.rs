@@ -0,0 +1,66 @@
+use crate::{clap_plugin_instance::ClapPluginInstance, host::SharedAudioProcessor};
+use godot::{
+    classes::{AudioStreamPlayback, IAudioStreamPlayback, native::AudioFrame},
+    prelude::*,
+};
+use std::slice;
+
+/// [`ClapAudioStream`](crate::clap_audio_stream::ClapAudioStream)的一次播放，持有一个独立的Clap插件实例。
+///
+/// 注意：插件总是以Godot的混音采样率渲染，`AudioStreamPlayer`的`pitch_scale`不会生效。
+#[derive(GodotClass)]
+#[class(no_init, base = AudioStreamPlayback)]
+pub struct ClapAudioStreamPlayback {
+    /// 插件实例在Godot端的代理，用于在主线程上向插件发送MIDI、修改参数等。
+    plugin_instance: Gd<ClapPluginInstance>,
+    /// 与插件实例共享的音频处理器，在Godot的混音线程上使用。
+    shared_audio_processor: SharedAudioProcessor,
+    is_playing: bool,
+}
+#[godot_api]
+impl ClapAudioStreamPlayback {
+    pub fn new(
+        plugin_instance: Gd<ClapPluginInstance>,
+        shared_audio_processor: SharedAudioProcessor,
+    ) -> Gd<Self> {
+        Gd::from_object(Self {
+            plugin_instance,
+            shared_audio_processor,
+            is_playing: false,
+        })
+    }
+
+    /// 本次播放所使用的插件实例，可以用于向插件发送MIDI、修改参数等。
+    #[func]
+    fn get_plugin_instance(&self) -> Gd<ClapPluginInstance> {
+        self.plugin_instance.clone()
+    }
+}
+#[godot_api]
+impl IAudioStreamPlayback for ClapAudioStreamPlayback {
+    fn start(&mut self, _from_pos: f64) {
+        self.is_playing = true;
+    }
+    fn stop(&mut self) {
+        self.is_playing = false;
+    }
+    fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    unsafe fn mix_rawptr(&mut self, buffer: *mut AudioFrame, _rate_scale: f32, frames: i32) -> i32 {
+        let frames_count = frames.max(0) as usize;
+        // SAFETY: Godot保证缓冲包含`frames`帧。
+        let output = unsafe { slice::from_raw_parts_mut(buffer, frames_count) };
+        self.shared_audio_processor.process(&[], output);
+        frames
+    }
+}
+impl Drop for ClapAudioStreamPlayback {
+    fn drop(&mut self) {
+        if self.plugin_instance.is_instance_valid() {
+            // 播放实例可能在混音线程上被释放，插件实例节点则只能在主线程上释放。
+            self.plugin_instance.call_deferred("queue_free", &[]);
+        }
+    }
+}
@@ -1,15 +1,15 @@
+use crate::ring_buffer::Producer;
 use godot::{
     classes::{AudioStream, AudioStreamPlayback},
     prelude::*,
 };
 use itertools::{EitherOrBoth, Itertools};
-use std::sync::mpsc::Sender;
 
 /// 对Clap插件输入通道的访问句柄。
 #[derive(GodotClass)]
 #[class(no_init)]
 pub struct ClapInputAudioChannelAccess {
-    channel_buffer_tx: Sender<Box<[f32]>>,
+    channel_buffer_tx: Producer<f32>,
 
     /// 通道接收音频流。当这个属性被设置后，会以所属Clap插件的采样率速度持续不断地从流中读取数据并发往音频线程对应的通道缓冲。
     ///
@@ -22,7 +22,7 @@ pub struct ClapInputAudioChannelAccess {
 }
 #[godot_api]
 impl ClapInputAudioChannelAccess {
-    pub fn new(channel_buffer_tx: Sender<Box<[f32]>>) -> Gd<Self> {
+    pub fn new(channel_buffer_tx: Producer<f32>) -> Gd<Self> {
         Gd::from_object(Self {
             channel_buffer_tx,
             stream: None,
@@ -30,13 +30,9 @@ impl ClapInputAudioChannelAccess {
         })
     }
 
-    fn push_buffer(&self, frames: Box<[f32]>) {
-        self.channel_buffer_tx.send(frames).unwrap_or_else(|err| {
-            godot_error!(
-                "ClapInputAudioChannelAccess对应的输入通道已不复存在：{:?}",
-                err
-            )
-        })
+    fn push_buffer(&mut self, frames: &[f32]) {
+        // 音频线程迟迟没有取走数据时，放不下的部分会被丢弃，以免延迟越积越多。
+        self.channel_buffer_tx.push_slice(frames);
     }
 
     pub fn process(&mut self, port_buffer: impl ExactSizeIterator<Item = f32>) {
@@ -55,7 +51,7 @@ impl ClapInputAudioChannelAccess {
             .map_or(&[] as &[Vector2], |buffer| buffer.as_slice());
 
         let buffer = buffer.iter().zip_longest(port_buffer);
-        let buffer = if is_monophonic {
+        let buffer: Vec<_> = if is_monophonic {
             buffer
                 .map(|frame| match frame {
                     EitherOrBoth::Both(channel_frame, port_frame) => channel_frame.x + port_frame,
@@ -83,7 +79,7 @@ impl ClapInputAudioChannelAccess {
                 .collect()
         };
 
-        self.push_buffer(buffer);
+        self.push_buffer(&buffer);
     }
 
     #[func]
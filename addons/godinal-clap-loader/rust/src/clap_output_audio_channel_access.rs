@@ -1,4 +1,7 @@
-use crate::weak_ref::{assert_from_weak_ref, assert_to_weak_ref};
+use crate::{
+    ring_buffer::Consumer,
+    weak_ref::{assert_from_weak_ref, assert_to_weak_ref},
+};
 use godot::{
     classes::{
         AudioStreamGenerator, AudioStreamGeneratorPlayback, AudioStreamPlayback, IAudioStream,
@@ -6,7 +9,7 @@ use godot::{
     },
     prelude::*,
 };
-use std::{cell::OnceCell, sync::mpsc::Receiver};
+use std::cell::OnceCell;
 
 /// 对Clap插件输出通道的访问句柄。
 ///
@@ -14,14 +17,14 @@ use std::{cell::OnceCell, sync::mpsc::Receiver};
 #[derive(GodotClass)]
 #[class(no_init, base=AudioStream)]
 pub struct ClapOutputAudioChannelAccess {
-    channel_buffer_rx: Receiver<Box<[f32]>>,
+    channel_buffer_rx: Consumer<f32>,
     audio_stream_generator: Gd<AudioStreamGenerator>,
     audio_stream_generator_playback_weak_refs: Array<Gd<WeakRef>>,
 }
 #[godot_api]
 impl ClapOutputAudioChannelAccess {
     pub fn new(
-        channel_buffer_rx: Receiver<Box<[f32]>>,
+        channel_buffer_rx: Consumer<f32>,
         audio_stream_generator: Gd<AudioStreamGenerator>,
     ) -> Gd<Self> {
         Gd::from_object(Self {
@@ -49,7 +52,13 @@ impl ClapOutputAudioChannelAccess {
     }
 
     pub fn process(&mut self) -> Box<[Box<[f32]>]> {
-        let buffer = self.channel_buffer_rx.try_iter().collect::<Box<_>>();
+        let mut frames = vec![0.0; self.channel_buffer_rx.len()];
+        self.channel_buffer_rx.pop_slice(&mut frames);
+        let buffer: Box<[Box<[f32]>]> = if frames.is_empty() {
+            Box::new([])
+        } else {
+            Box::new([frames.into_boxed_slice()])
+        };
 
         let frames = OnceCell::<PackedVector2Array>::new();
         for mut audio_stream_generator_playback in self.audio_stream_generator_playbacks() {
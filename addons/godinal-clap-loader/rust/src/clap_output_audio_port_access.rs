@@ -1,4 +1,5 @@
 use crate::{
+    clap_audio_recorder::ClapAudioRecorder,
     clap_output_audio_channel_access::ClapOutputAudioChannelAccess,
     weak_ref::{assert_from_weak_ref, assert_to_weak_ref},
 };
@@ -16,12 +17,15 @@ use std::{cell::OnceCell, ops::Deref};
 /// 可以被当作[`IAudioStream`]使用，需要注意[`IAudioStream`]仅支持两个声道输出：
 /// - 当本Clap输出端口包含少于两个通道时，左右声道的音频数据均来自Clap插件的第一个输出通道。
 /// - 当本Clap输出端口包含多余两个通道时，第三个及之后的通道的数据并不会通过[`IAudioStream`]的方式传递出来。如有访问这些通道的需要，请使用[`ClapOutputAudioPortAccess::channel_accesses`]。
+///
+/// 需要把所有通道录制到文件时，请使用[`ClapOutputAudioPortAccess::create_recorder`]。
 #[derive(GodotClass)]
 #[class(no_init,base = AudioStream)]
 pub struct ClapOutputAudioPortAccess {
     channel_accesses: Box<[Gd<ClapOutputAudioChannelAccess>]>,
     audio_stream_generator: Gd<AudioStreamGenerator>,
     audio_stream_generator_playback_weak_refs: Array<Gd<WeakRef>>,
+    recorder_weak_refs: Array<Gd<WeakRef>>,
     /// 由于左右两个通道不一定会同时收到数据，需要这个缓冲同步两个通道的数据长度。
     stereo_sync_buffer: Option<StereoSyncBuffer<f32>>,
 }
@@ -36,6 +40,7 @@ impl ClapOutputAudioPortAccess {
             stereo_sync_buffer: None,
             audio_stream_generator,
             audio_stream_generator_playback_weak_refs: Array::new(),
+            recorder_weak_refs: Array::new(),
         })
     }
 
@@ -56,6 +61,20 @@ impl ClapOutputAudioPortAccess {
         audio_stream_generator_playbacks
     }
 
+    fn recorders(&mut self) -> Box<[Gd<ClapAudioRecorder>]> {
+        let recorders = self
+            .recorder_weak_refs
+            .iter_shared()
+            .filter_map(|weak_ref| assert_from_weak_ref(&weak_ref))
+            .collect::<Box<[Gd<ClapAudioRecorder>]>>();
+
+        self.recorder_weak_refs.clear();
+        self.recorder_weak_refs
+            .extend(recorders.iter().filter_map(assert_to_weak_ref));
+
+        recorders
+    }
+
     pub fn process(&mut self) -> Box<[Box<[Box<[f32]>]>]> {
         let buffers = self
             .channel_accesses
@@ -63,6 +82,10 @@ impl ClapOutputAudioPortAccess {
             .map(|channel_access| channel_access.bind_mut().process())
             .collect::<Box<_>>();
 
+        for mut recorder in self.recorders() {
+            recorder.bind_mut().push(&buffers);
+        }
+
         let Some(left_channel_buffer) = buffers.get(0) else {
             return Box::new([]);
         };
@@ -96,6 +119,21 @@ impl ClapOutputAudioPortAccess {
     fn channel_accesses(&self) -> Array<Gd<ClapOutputAudioChannelAccess>> {
         self.channel_accesses.iter().cloned().collect()
     }
+
+    /// 创建一个录制本端口所有通道的录音机。
+    ///
+    /// 端口只持有录音机的弱引用，录音机需要由调用者持有，被释放后就不再接收音频。
+    #[func]
+    fn create_recorder(&mut self) -> Gd<ClapAudioRecorder> {
+        let recorder = ClapAudioRecorder::new(
+            self.channel_accesses.len(),
+            self.audio_stream_generator.get_mix_rate() as f64,
+        );
+        if let Some(recorder_weak_ref) = assert_to_weak_ref(&recorder) {
+            self.recorder_weak_refs.push(&recorder_weak_ref);
+        }
+        recorder
+    }
 }
 #[godot_api]
 impl IAudioStream for ClapOutputAudioPortAccess {
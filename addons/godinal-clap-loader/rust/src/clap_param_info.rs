@@ -0,0 +1,139 @@
+use clack_extensions::params::{ParamInfo, ParamInfoFlags};
+use godot::{
+    global::{PropertyHint, PropertyUsageFlags},
+    meta::{PropertyHintInfo, PropertyInfo},
+    prelude::*,
+};
+
+/// Clap插件参数的描述信息，由`ClapPluginInstance`的`param_infos`方法获取。
+#[derive(GodotClass)]
+#[class(no_init)]
+pub struct ClapParamInfo {
+    /// 参数编号，在所属插件实例中唯一，用于读写参数的值。
+    #[var(get)]
+    id: u32,
+    /// 参数名称。
+    #[var(get)]
+    name: GString,
+    /// 参数所属的模块路径，以`/`分隔，例如`Oscillators/Wavetable 1`，可能为空。
+    #[var(get)]
+    module: GString,
+    /// 参数的最小值。
+    #[var(get)]
+    min_value: f64,
+    /// 参数的最大值。
+    #[var(get)]
+    max_value: f64,
+    /// 参数的默认值。
+    #[var(get)]
+    default_value: f64,
+    /// 参数的标志位，可以和本类的`IS_*`常量做按位与运算来判断参数的性质。
+    #[var(get)]
+    flags: u32,
+    /// 参数在`ClapPluginInstance`上对应的Godot属性名，格式为`params/模块路径/参数名称`。
+    /// 可以直接用于`get`、`set`、`Tween`和`AnimationPlayer`的属性路径。
+    #[var(get)]
+    property_name: StringName,
+}
+#[godot_api]
+impl ClapParamInfo {
+    /// 参数值只能取整数。
+    #[constant]
+    const IS_STEPPED: i64 = ParamInfoFlags::IS_STEPPED.bits() as i64;
+    /// 参数值是周期性的，例如相位。
+    #[constant]
+    const IS_PERIODIC: i64 = ParamInfoFlags::IS_PERIODIC.bits() as i64;
+    /// 参数不应该被展示给用户。
+    #[constant]
+    const IS_HIDDEN: i64 = ParamInfoFlags::IS_HIDDEN.bits() as i64;
+    /// 参数只读，宿主无法修改。
+    #[constant]
+    const IS_READONLY: i64 = ParamInfoFlags::IS_READONLY.bits() as i64;
+    /// 参数是插件的旁通开关。
+    #[constant]
+    const IS_BYPASS: i64 = ParamInfoFlags::IS_BYPASS.bits() as i64;
+    /// 参数可以被自动化。
+    #[constant]
+    const IS_AUTOMATABLE: i64 = ParamInfoFlags::IS_AUTOMATABLE.bits() as i64;
+    /// 参数可以被调制。
+    #[constant]
+    const IS_MODULATABLE: i64 = ParamInfoFlags::IS_MODULATABLE.bits() as i64;
+
+    pub fn new(param_info: &ParamInfo, property_name: StringName) -> Gd<Self> {
+        Gd::from_object(Self {
+            id: param_info.id.get(),
+            name: String::from_utf8_lossy(param_info.name).to_godot(),
+            module: String::from_utf8_lossy(param_info.module).to_godot(),
+            min_value: param_info.min_value,
+            max_value: param_info.max_value,
+            default_value: param_info.default_value,
+            flags: param_info.flags.bits(),
+            property_name,
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    pub fn default_value(&self) -> f64 {
+        self.default_value
+    }
+    pub fn property_name(&self) -> &StringName {
+        &self.property_name
+    }
+
+    /// 参数作为Godot属性时的属性信息，隐藏的参数不会作为属性暴露。
+    pub fn property_info(&self) -> Option<PropertyInfo> {
+        if self.has_flag(Self::IS_HIDDEN) {
+            return None;
+        }
+
+        let step = if self.has_flag(Self::IS_STEPPED) {
+            1.0
+        } else {
+            self.continuous_step()
+        };
+        let usage = if self.is_readonly() {
+            PropertyUsageFlags::EDITOR | PropertyUsageFlags::READ_ONLY
+        } else {
+            PropertyUsageFlags::EDITOR
+        };
+        Some(PropertyInfo {
+            hint_info: PropertyHintInfo {
+                hint: PropertyHint::RANGE,
+                hint_string: format!("{},{},{step}", self.min_value, self.max_value).to_godot(),
+            },
+            usage,
+            ..PropertyInfo::new_var::<f64>(&self.property_name.to_string())
+        })
+    }
+
+    /// 连续参数在检查器中的步长，取参数范围所在数量级的万分之一，例如范围为`[0, 1]`时为`0.0001`，`[20, 20000]`时为`1`。
+    fn continuous_step(&self) -> f64 {
+        let range = self.max_value - self.min_value;
+        if !range.is_finite() || range <= 0.0 {
+            return 0.0001;
+        }
+        10f64.powi(range.log10().floor() as i32 - 4)
+    }
+
+    pub fn is_modulatable(&self) -> bool {
+        self.has_flag(Self::IS_MODULATABLE)
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.has_flag(Self::IS_READONLY)
+    }
+
+    /// 将参数值限制在参数的取值范围内。
+    pub fn clamp_value(&self, value: f64) -> f64 {
+        // 不使用`f64::clamp`，防止插件给出的最小值大于最大值时直接panic。
+        value.max(self.min_value).min(self.max_value)
+    }
+
+    /// 是否包含给定的标志位。
+    #[func]
+    fn has_flag(&self, flag: i64) -> bool {
+        self.flags as i64 & flag != 0
+    }
+}
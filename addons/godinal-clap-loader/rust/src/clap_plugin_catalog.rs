@@ -0,0 +1,177 @@
+use crate::{
+    clap_plugin_descriptor::ClapPluginDescriptor,
+    host::{ClapFileError, load_plugin_bundle},
+};
+use godot::prelude::*;
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Clap插件目录，在Clap标准搜索路径和用户指定的目录中查找clap文件，并列出其中所有插件的描述信息。
+///
+/// 扫描时只会读取插件工厂中的描述信息，不会实例化或激活插件，可以用来制作插件浏览器。
+/// 找到的插件可以通过`ClapPluginInstance`的`new_from_clap_file_with_id`单独加载。
+///
+/// 标准搜索路径：
+/// - 所有平台：环境变量`CLAP_PATH`中列出的目录。
+/// - Linux：`~/.clap`、`/usr/lib/clap`。
+/// - Windows：`%COMMONPROGRAMFILES%\CLAP`、`%LOCALAPPDATA%\Programs\Common\CLAP`。
+/// - macOS：`/Library/Audio/Plug-Ins/CLAP`、`~/Library/Audio/Plug-Ins/CLAP`。
+#[derive(GodotClass)]
+#[class(init, base = RefCounted)]
+pub struct ClapPluginCatalog {
+    /// 除标准搜索路径以外，额外需要扫描的目录。
+    #[var]
+    extra_search_paths: PackedStringArray,
+    /// 最近一次扫描得到的插件描述信息。
+    descriptors: Array<Gd<ClapPluginDescriptor>>,
+}
+#[godot_api]
+impl ClapPluginCatalog {
+    /// Clap标准搜索路径，不存在的目录也会被列出。
+    #[func]
+    fn standard_search_paths() -> PackedStringArray {
+        standard_search_paths()
+            .iter()
+            .map(|path| path.to_string_lossy().to_godot())
+            .collect()
+    }
+
+    /// 扫描标准搜索路径和`extra_search_paths`中的所有clap文件（包括子目录），
+    /// 读取其中所有插件的描述信息。无法加载的clap文件会被跳过并打印警告。
+    ///
+    /// 返回：扫描到的插件描述信息，也可以之后通过`get_descriptors`再次获取。
+    #[func]
+    fn scan(&mut self) -> Array<Gd<ClapPluginDescriptor>> {
+        let search_paths = standard_search_paths().into_iter().chain(
+            self.extra_search_paths
+                .as_slice()
+                .iter()
+                .map(|path| PathBuf::from(path.to_string())),
+        );
+
+        let mut visited_directories = HashSet::new();
+        let mut clap_files = Vec::new();
+        for search_path in search_paths {
+            find_clap_files(&search_path, &mut visited_directories, &mut clap_files);
+        }
+
+        self.descriptors = clap_files
+            .iter()
+            .filter_map(|clap_file| match read_descriptors(clap_file) {
+                Ok(descriptors) => Some(descriptors),
+                Err(err) => {
+                    godot_warn!("{err}");
+                    None
+                }
+            })
+            .flatten()
+            .collect();
+        self.descriptors.clone()
+    }
+
+    /// 最近一次扫描得到的插件描述信息，还没有扫描过时为空。
+    #[func]
+    fn get_descriptors(&self) -> Array<Gd<ClapPluginDescriptor>> {
+        self.descriptors.clone()
+    }
+
+    /// 在最近一次扫描的结果中查找插件。
+    /// - `plugin_id`: 插件编号，例如`com.u-he.diva`。
+    ///
+    /// 返回：找到的插件描述信息，找不到时为空。
+    #[func]
+    fn find_descriptor(&self, plugin_id: GString) -> Option<Gd<ClapPluginDescriptor>> {
+        self.descriptors
+            .iter_shared()
+            .find(|descriptor| *descriptor.bind().id() == plugin_id)
+    }
+}
+
+/// Clap标准搜索路径，见<https://github.com/free-audio/clap/blob/main/include/clap/entry.h>。
+fn standard_search_paths() -> Vec<PathBuf> {
+    let mut search_paths = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(home) = env::var_os("HOME") {
+            search_paths.push(Path::new(&home).join(".clap"));
+        }
+        search_paths.push(PathBuf::from("/usr/lib/clap"));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(common_program_files) = env::var_os("COMMONPROGRAMFILES") {
+            search_paths.push(Path::new(&common_program_files).join("CLAP"));
+        }
+        if let Some(local_app_data) = env::var_os("LOCALAPPDATA") {
+            search_paths.push(
+                Path::new(&local_app_data)
+                    .join("Programs")
+                    .join("Common")
+                    .join("CLAP"),
+            );
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        search_paths.push(PathBuf::from("/Library/Audio/Plug-Ins/CLAP"));
+        if let Some(home) = env::var_os("HOME") {
+            search_paths.push(Path::new(&home).join("Library/Audio/Plug-Ins/CLAP"));
+        }
+    }
+
+    if let Some(clap_path) = env::var_os("CLAP_PATH") {
+        search_paths.extend(env::split_paths(&clap_path));
+    }
+    search_paths
+}
+
+/// 递归查找目录中的clap文件。
+///
+/// macOS上的clap文件是一个目录形式的包，所以名称以`.clap`结尾的目录也会被当作clap文件，且不再深入。
+/// - `visited_directories`: 已经扫描过的目录，防止符号链接成环或搜索路径重复时重复扫描。
+fn find_clap_files(
+    directory: &Path,
+    visited_directories: &mut HashSet<PathBuf>,
+    clap_files: &mut Vec<PathBuf>,
+) {
+    let Ok(canonical_directory) = directory.canonicalize() else {
+        return;
+    };
+    if !visited_directories.insert(canonical_directory) {
+        return;
+    }
+    let Ok(entries) = directory.read_dir() else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("clap"))
+        {
+            clap_files.push(path);
+        } else if path.is_dir() {
+            find_clap_files(&path, visited_directories, clap_files);
+        }
+    }
+}
+
+/// 读取clap文件中所有插件的描述信息，不会实例化插件。
+fn read_descriptors(path: &Path) -> Result<Vec<Gd<ClapPluginDescriptor>>, ClapFileError> {
+    let plugin_bundle = load_plugin_bundle(path)?;
+    let plugin_factory =
+        plugin_bundle
+            .get_plugin_factory()
+            .ok_or(ClapFileError::NoPluginFactory {
+                path: path.to_path_buf(),
+            })?;
+    Ok(plugin_factory
+        .into_iter()
+        .filter_map(|plugin_descriptor| ClapPluginDescriptor::new(path, &plugin_descriptor))
+        .collect())
+}
@@ -0,0 +1,77 @@
+use clack_host::factory::PluginDescriptor;
+use godot::prelude::*;
+use std::{ffi::CStr, path::Path};
+
+/// Clap插件的描述信息。
+///
+/// 实例化之前可以由`ClapPluginCatalog`扫描得到（不会激活插件），实例化之后可以由`ClapPluginInstance`的`plugin_descriptor`方法获取。
+#[derive(GodotClass)]
+#[class(no_init)]
+pub struct ClapPluginDescriptor {
+    /// 插件所在的clap文件路径。
+    #[var(get)]
+    path: GString,
+    /// 插件编号，在所有Clap插件中唯一，例如`com.u-he.diva`。
+    #[var(get)]
+    id: GString,
+    /// 插件名称。
+    #[var(get)]
+    name: GString,
+    /// 插件厂商。
+    #[var(get)]
+    vendor: GString,
+    /// 插件的主页地址。
+    #[var(get)]
+    url: GString,
+    /// 插件的使用手册地址。
+    #[var(get)]
+    manual_url: GString,
+    /// 插件的技术支持地址。
+    #[var(get)]
+    support_url: GString,
+    /// 插件版本，格式由插件自行决定，通常形如`1.4.4`。
+    #[var(get)]
+    version: GString,
+    /// 插件的简短介绍。
+    #[var(get)]
+    description: GString,
+    /// 插件的特性标签，例如`instrument`、`audio-effect`、`reverb`，可以用于筛选插件。
+    /// 标准标签见<https://github.com/free-audio/clap/blob/main/include/clap/plugin-features.h>。
+    #[var(get)]
+    features: PackedStringArray,
+}
+#[godot_api]
+impl ClapPluginDescriptor {
+    /// 构造，插件描述信息中没有编号时返回空。
+    pub fn new(path: &Path, plugin_descriptor: &PluginDescriptor) -> Option<Gd<Self>> {
+        let optional_member =
+            |member: Option<&CStr>| member.map(c_str_to_godot).unwrap_or_default();
+        Some(Gd::from_object(Self {
+            path: path.to_string_lossy().to_godot(),
+            id: c_str_to_godot(plugin_descriptor.id()?),
+            name: optional_member(plugin_descriptor.name()),
+            vendor: optional_member(plugin_descriptor.vendor()),
+            url: optional_member(plugin_descriptor.url()),
+            manual_url: optional_member(plugin_descriptor.manual_url()),
+            support_url: optional_member(plugin_descriptor.support_url()),
+            version: optional_member(plugin_descriptor.version()),
+            description: optional_member(plugin_descriptor.description()),
+            features: plugin_descriptor.features().map(c_str_to_godot).collect(),
+        }))
+    }
+
+    pub fn id(&self) -> &GString {
+        &self.id
+    }
+
+    /// 是否带有给定的特性标签。
+    /// - `feature`: 特性标签，例如`instrument`。
+    #[func]
+    fn has_feature(&self, feature: GString) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+fn c_str_to_godot(c_str: &CStr) -> GString {
+    c_str.to_string_lossy().to_godot()
+}
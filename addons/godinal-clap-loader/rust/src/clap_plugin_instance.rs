@@ -1,29 +1,50 @@
 use crate::{
     clap_input_audio_port_access::ClapInputAudioPortAccess,
     clap_output_audio_port_access::ClapOutputAudioPortAccess,
+    clap_param_info::ClapParamInfo,
+    clap_plugin_descriptor::ClapPluginDescriptor,
     clap_transport_event_access::ClapTransportEventAccess,
-    host::{Host, host_handlers_impl::host_shared::HostShared},
-    midi::{event_to_midi, midi_to_event},
+    host::{
+        AudioDriverMode, EventTime, Host, HostEvent, ScheduledEvent, SharedAudioProcessor,
+        host_handlers_impl::host_shared::HostShared,
+    },
+    midi::{
+        MidiConversionError, event_to_midi, event_to_midi_bytes, event_to_ump,
+        midi_bytes_to_events, pckn_from_godot, sys_ex_to_event, ump_to_events,
+    },
+    wav::{WavSampleFormat, WavWriter, encode_interleaved},
 };
 use clack_extensions::{
     gui::HostGuiImpl,
     log::{HostLogImpl, LogSeverity},
+    note_ports::NoteDialects,
+    params::ParamRescanFlags,
+};
+use clack_host::{
+    events::{Pckn, UnknownEvent, spaces::CoreEventSpace},
+    utils::ClapId,
 };
 #[allow(unused)]
 use godot::global::MidiMessage;
 use godot::{
     classes::{
-        DisplayServer, InputEventMidi, Window, display_server::WindowMode, node::InternalMode,
-        notify::NodeNotification, window::WindowInitialPosition,
+        AudioServer, AudioStream, AudioStreamWAV, DisplayServer, Engine, InputEventMidi,
+        ProjectSettings, SceneTree, Window, audio_stream_wav, display_server::WindowMode,
+        node::InternalMode, notify::NodeNotification, window::WindowInitialPosition,
     },
+    meta::PropertyInfo,
     prelude::*,
 };
-use std::path::Path;
+use itertools::Itertools;
+use std::{fs::File, io::BufWriter, path::Path};
 
 /// Clap插件实例在Godot端的代理。
+///
+/// 插件的每个参数（隐藏参数除外）都会作为一个动态属性暴露出来，属性名见[`ClapParamInfo`]的`property_name`，
+/// 所以可以在检查器中查看和修改参数，也可以用`Tween`和`AnimationPlayer`对参数做动画。
 #[derive(GodotClass)]
 #[class(no_init, base = Node)]
-struct ClapPluginInstance {
+pub struct ClapPluginInstance {
     #[base]
     base: Base<Node>,
 
@@ -38,19 +59,78 @@ struct ClapPluginInstance {
     #[allow(unused)]
     #[var(get = get_state, set = set_state)]
     state: PackedByteArray,
+
+    /// 插件是否正在睡眠。
+    ///
+    /// 插件表示自己暂时不会产生声音时（例如合成器的所有音符都已结束，或效果器的拖尾已经播完），
+    /// 宿主会让插件睡眠，不再调用插件的处理，直到有新的输入音频或事件为止，以节省CPU。
+    #[allow(unused)]
+    #[var(get = is_sleeping)]
+    is_sleeping: bool,
 }
 impl ClapPluginInstance {
     /// 构造
-    fn new(host: Host) -> Gd<Self> {
+    pub fn new(host: Host) -> Gd<Self> {
         let mut clap_plugin_instance = Gd::from_init_fn(|base| Self {
             base,
             host,
             clap_transport_event_access: None,
             state: PackedByteArray::new(),
+            is_sleeping: false,
         });
         clap_plugin_instance.set_process_internal(true);
         clap_plugin_instance
     }
+    /// 加载由外部驱动音频处理的插件实例，例如由Godot的混音线程驱动，采样率与Godot的混音采样率一致。
+    ///
+    /// 插件实例会被添加为场景树根节点的子节点，以便在主线程上处理插件的消息。
+    /// - `path`: clap插件文件系统路径，也可以是`res://`之类的Godot路径。
+    /// - `plugin_id`: 插件编号。
+    /// - `max_frames_count_seconds`: 外部一次处理的最大秒数。
+    ///
+    /// 返回：插件实例，以及在外部的音频线程上进行处理所用的音频处理器。
+    pub fn new_externally_driven(
+        path: &GString,
+        plugin_id: &GString,
+        max_frames_count_seconds: f64,
+    ) -> Option<(Gd<Self>, SharedAudioProcessor)> {
+        let path = ProjectSettings::singleton()
+            .globalize_path(path)
+            .to_string();
+        let host_build_result = match Host::try_new_from_clap_file_with_id(
+            Path::new(&path),
+            &plugin_id.to_string(),
+            AudioServer::singleton().get_mix_rate() as f64,
+            max_frames_count_seconds,
+            AudioDriverMode::External,
+        ) {
+            Ok(host_build_result) => host_build_result,
+            Err(err) => {
+                godot_error!("{err}");
+                return None;
+            }
+        };
+        let host = match host_build_result {
+            Ok(host) => host,
+            Err(err) => {
+                godot_error!("{err}");
+                return None;
+            }
+        };
+        let shared_audio_processor = host.shared_audio_processor()?.clone();
+        let clap_plugin_instance = Self::new(host);
+
+        let scene_tree = Engine::singleton()
+            .get_main_loop()
+            .and_then(|main_loop| main_loop.try_cast::<SceneTree>().ok());
+        if let Some(mut root) = scene_tree.and_then(|scene_tree| scene_tree.get_root()) {
+            root.call_deferred("add_child", &[clap_plugin_instance.to_variant()]);
+        } else {
+            godot_warn!("找不到场景树，插件实例需要手动添加到场景树中才能处理插件的消息");
+        }
+
+        Some((clap_plugin_instance, shared_audio_processor))
+    }
     /// 初始化Clap插件的Godot窗口
     fn init_window(&mut self) {
         if self.host.message_processor().window().is_none() {
@@ -116,10 +196,187 @@ impl ClapPluginInstance {
     fn log(&self, severity: LogSeverity, message: &str) {
         self.host_shared().log(severity, message)
     }
+
+    /// 处理插件输出的事件，将其转发为信号。
+    fn process_output_events(&mut self) {
+        let events = self.host.audio_access().pop_output_events();
+
+        let mut midi_received = Array::new();
+        for output_event in events {
+            if self.process_param_event(output_event.event()) {
+                continue;
+            }
+            if let Some((port_index, words)) = event_to_ump(output_event.event()) {
+                self.signals().ump_received().emit(
+                    port_index,
+                    &PackedInt32Array::from_iter(words.into_iter().map(|word| word as i32)),
+                    output_event.steady_time,
+                );
+                continue;
+            }
+            if let Some((port_index, bytes)) = event_to_midi_bytes(&output_event) {
+                self.signals().midi_bytes_received().emit(
+                    port_index,
+                    &PackedByteArray::from(bytes.as_slice()),
+                    output_event.steady_time,
+                );
+                // 不跳过：`midi_received`在原始midi信号出现之前就会发出插件输出的midi消息，
+                // 只关心`InputEventMidi`的脚本仍然需要收到它们。
+            }
+            if let Some(midi) = event_to_midi(output_event.event()) {
+                midi_received.push(&midi);
+            }
+        }
+
+        // 插件midi事件触发。
+        if !midi_received.is_empty() {
+            self.signals().midi_received().emit(&midi_received);
+        }
+    }
+
+    /// 处理插件输出的参数事件（通常来自用户对插件GUI的操作）。
+    ///
+    /// 返回：是否是参数事件。
+    fn process_param_event(&mut self, event: &UnknownEvent) -> bool {
+        match event.as_core_event() {
+            Some(CoreEventSpace::ParamValue(param_value_event)) => {
+                if let Some(param_id) = param_value_event.param_id() {
+                    let value = param_value_event.value();
+                    self.host.param_access().set_cached_value(param_id, value);
+                    self.signals().param_changed().emit(param_id.get(), value);
+                }
+                true
+            }
+            Some(CoreEventSpace::ParamGestureBegin(param_gesture_begin_event)) => {
+                if let Some(param_id) = param_gesture_begin_event.param_id() {
+                    self.signals().param_gesture_begin().emit(param_id.get());
+                }
+                true
+            }
+            Some(CoreEventSpace::ParamGestureEnd(param_gesture_end_event)) => {
+                if let Some(param_id) = param_gesture_end_event.param_id() {
+                    self.signals().param_gesture_end().emit(param_id.get());
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 处理主机事件，将其转发为信号。
+    fn process_host_event(&mut self, host_event: HostEvent) {
+        match host_event {
+            HostEvent::ParamsRescanned(flags) => {
+                if flags.intersects(ParamRescanFlags::INFO | ParamRescanFlags::ALL) {
+                    // 参数列表可能变了，需要让检查器重新获取动态属性。
+                    self.base_mut().notify_property_list_changed();
+                }
+                self.signals().params_rescanned().emit(flags.bits());
+            }
+            HostEvent::LatencyChanged(latency_frames) => {
+                self.signals().latency_changed().emit(latency_frames);
+            }
+            HostEvent::WokeUp => self.signals().woke_up().emit(),
+        }
+    }
+
+    /// 离线渲染，失败时打印错误并返回空，见[`Host::render_offline`]。
+    /// - `events`: 格式见[`Self::render_offline`]。
+    fn render(
+        &mut self,
+        frames_count: usize,
+        input_channels: Box<[Box<[f32]>]>,
+        events: &Array<Dictionary>,
+    ) -> Option<Box<[Box<[f32]>]>> {
+        let Some(events) = events
+            .iter_shared()
+            .map(|event| {
+                let frame_offset = event.get("frame")?.try_to::<u32>().ok()?;
+                let midi = event.get("midi")?.try_to::<Gd<InputEventMidi>>().ok()?;
+                Some((frame_offset, midi))
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            godot_error!("离线渲染失败，事件需要是包含`frame`和`midi`两个键的字典");
+            return None;
+        };
+        let events = match events
+            .into_iter()
+            .map(|(frame_offset, midi)| {
+                let event = self.host.midi_to_event(midi)?;
+                Ok(ScheduledEvent::new(
+                    EventTime::FrameOffset(frame_offset),
+                    event,
+                ))
+            })
+            .collect::<Result<_, MidiConversionError>>()
+        {
+            Ok(events) => events,
+            Err(err) => {
+                godot_error!("离线渲染失败，{err}");
+                return None;
+            }
+        };
+
+        let output_channels = self
+            .host
+            .render_offline(frames_count, input_channels, events);
+        if output_channels.is_none() {
+            godot_error!("离线渲染失败，插件已经停止处理");
+        }
+        output_channels
+    }
+
+    /// 离线渲染`seconds`秒，`input_stream`的左右声道作为前两个输入通道，见[`Self::bounce_to_audio_stream_wav`]。
+    fn bounce(
+        &mut self,
+        seconds: f64,
+        input_stream: Option<Gd<AudioStream>>,
+        events: &Array<Dictionary>,
+    ) -> Option<Box<[Box<[f32]>]>> {
+        let sample_rate = self.host.sample_rate();
+        let frames_count = seconds * sample_rate;
+        if !(0.0..i32::MAX as f64).contains(&frames_count) {
+            godot_error!("离线渲染失败，时长{seconds}秒不合法");
+            return None;
+        }
+        let frames_count = frames_count.round() as usize;
+
+        let input_channels: Box<[Box<[f32]>]> = match input_stream {
+            Some(mut input_stream) => {
+                let Some(mut playback) = input_stream.instantiate_playback() else {
+                    godot_error!("离线渲染失败，无法播放作为输入的音频流");
+                    return None;
+                };
+                // 音频流按Godot的混音采样率输出，需要换算到插件的采样率。
+                let rate_scale = AudioServer::singleton().get_mix_rate() as f64 / sample_rate;
+                playback.start();
+                let frames = playback.mix_audio(rate_scale as f32, frames_count as i32);
+                playback.stop();
+                let (left, right): (Vec<_>, Vec<_>) = frames
+                    .as_slice()
+                    .iter()
+                    .map(|frame| (frame.x, frame.y))
+                    .unzip();
+                Box::new([left.into_boxed_slice(), right.into_boxed_slice()])
+            }
+            None => Box::new([]),
+        };
+
+        let output_channels = self.render(frames_count, input_channels, events)?;
+        if output_channels.is_empty() {
+            godot_error!("离线渲染失败，插件没有输出通道");
+            return None;
+        }
+        Some(output_channels)
+    }
 }
 #[godot_api]
 impl ClapPluginInstance {
     /// 通过文件路径，加载内部包含的所有Clap插件。
+    ///
+    /// 这样加载的插件由独立的音频线程按墙上时钟处理，需要权衡`max_latency_seconds`。
+    /// 只需要播放插件的输出时，可以改用由Godot混音线程按需拉取的`ClapAudioStream`。
     /// - `path`: clap插件文件系统路径。
     /// - `sample_rate`: 采样率，决定了本处理实例读取和写入音频缓冲的整体帧速率。
     /// - `max_latency_seconds`: 最大延迟秒数。代表着Clap插件处理完音频信号之后，这些输出信号最多会在缓存里面留多长时间。用这个参数乘上采样率就是缓冲区的最大帧长度。[color=yellow]注意：这个值不要过小，否则clap插件线程数据的读取线程遭遇帧率扰动时会容易导致卡顿。如果这个值小于读取线程的最小帧间隔（指`_process`的`delta`），则会无法正常读取音频数据。[/color][color=red]也不要太大，内存会炸的。[/color]
@@ -157,6 +414,46 @@ impl ClapPluginInstance {
             .collect()
     }
 
+    /// 通过文件路径和插件编号，只加载clap文件中的指定插件。
+    ///
+    /// 一个clap文件可能包含大量插件，而`new_from_clap_file`会实例化并激活其中的每一个，只需要其中一个插件时应使用本方法。
+    /// - `path`: clap插件文件系统路径。
+    /// - `plugin_id`: 插件编号，例如`com.u-he.diva`，可以由`ClapPluginCatalog`扫描得到。
+    /// - `sample_rate`: 采样率，决定了本处理实例读取和写入音频缓冲的整体帧速率。
+    /// - `max_latency_seconds`: 最大延迟秒数，见`new_from_clap_file`。
+    ///
+    /// 返回：加载的clap实例，文件中找不到该插件或加载失败时为空。
+    #[func]
+    fn new_from_clap_file_with_id(
+        path: GString,
+        plugin_id: GString,
+        sample_rate: f64,
+        max_latency_seconds: f64,
+    ) -> Option<Gd<Self>> {
+        let path = path.to_string();
+        let path = Path::new(&path);
+        let host_build_result = match Host::try_new_from_clap_file_with_id(
+            path,
+            &plugin_id.to_string(),
+            sample_rate,
+            max_latency_seconds,
+            AudioDriverMode::Thread,
+        ) {
+            Ok(host_build_result) => host_build_result,
+            Err(err) => {
+                godot_error!("{err}");
+                return None;
+            }
+        };
+        match host_build_result {
+            Ok(host) => Some(Self::new(host)),
+            Err(err) => {
+                godot_error!("{err}");
+                None
+            }
+        }
+    }
+
     /// 通过多个文件路径，加载内部包含的所有Clap插件。
     /// - `paths`: 各个clap插件文件系统路径。
     /// - `sample_rate`: 采样率，决定了本处理实例读取和写入音频缓冲的整体帧速率。
@@ -187,6 +484,12 @@ impl ClapPluginInstance {
         self.host.message_processor().plugin_desc().to_godot()
     }
 
+    /// 插件的完整描述信息，包括厂商、版本、特性标签等。
+    #[func]
+    fn plugin_descriptor(&self) -> Gd<ClapPluginDescriptor> {
+        self.host.plugin_descriptor().clone()
+    }
+
     /// 显示插件GUI。
     #[func]
     fn show_gui(&self) {
@@ -233,20 +536,477 @@ impl ClapPluginInstance {
 
     /// 向插件发送midi事件。
     ///
-    /// 目前支持的[`MidiMessage`]：
-    /// - [`MidiMessage::NOTE_ON`]
-    /// - [`MidiMessage::NOTE_OFF`]
-    /// - [`MidiMessage::CONTROL_CHANGE`]
+    /// 事件的`device`即插件的输入音符端口下标，按端口支持的音符方言协商发送的方式：
+    /// 优先使用插件偏好的方言，否则优先使用Clap音符事件。
+    /// 以Clap方言发送时，音符开、音符关和力度感应会转换为Clap的音符事件，
+    /// 其余消息（包括控制变化）则作为原始的midi数据发送，需要端口支持midi方言，转换方式见`midi_to_event`。
+    /// 无法转换的事件（例如取值超出midi协议范围，或端口不支持所需的方言）会被跳过并打印错误。
     #[func]
     fn send_midi(&self, midi: Array<Gd<InputEventMidi>>) {
-        let events = midi.iter_shared().map(|midi| midi_to_event(midi)).collect();
+        let events = midi
+            .iter_shared()
+            .filter_map(|midi| {
+                self.host
+                    .midi_to_event(midi)
+                    .inspect_err(|err| godot_error!("发送midi事件失败，{err}"))
+                    .ok()
+            })
+            .collect();
         self.host.audio_access().send_input_event_buffers(events);
     }
 
+    /// 向插件发送原始的midi 1.0数据，用于[`InputEventMidi`]无法表达的消息，或偏好原始midi的插件。
+    ///
+    /// `bytes`可以包含连续的多条消息，支持省略状态字节的运行状态和以`0xF0`开头、`0xF7`结尾的系统独占消息，
+    /// 所有消息都在同一帧生效。含有无法识别的字节时整段数据都不会发送，并打印错误。
+    /// 只有支持midi方言的音符端口才能接收，见[`Self::get_note_port_dialects`]。
+    /// - `port`: 插件的输入音符端口下标。
+    /// - `bytes`: 原始的midi字节。
+    /// - `frame_offset`: 相对于音频线程下一次处理开始时的帧偏移。
+    #[func]
+    fn send_midi_bytes(&self, port: u16, bytes: PackedByteArray, frame_offset: u32) {
+        match self
+            .host
+            .check_note_dialect(port, NoteDialects::MIDI, "midi")
+            .and_then(|()| {
+                midi_bytes_to_events(port, bytes.as_slice(), EventTime::FrameOffset(frame_offset))
+            }) {
+            Ok(events) => self
+                .host
+                .audio_access()
+                .send_scheduled_input_events(events.into()),
+            Err(err) => godot_error!("发送midi数据失败，{err}"),
+        }
+    }
+
+    /// 向插件发送一条系统独占消息（SysEx），例如硬件合成器的音色数据。
+    ///
+    /// 只有支持midi方言的音符端口才能接收，见[`Self::get_note_port_dialects`]。
+    /// - `port`: 插件的输入音符端口下标。
+    /// - `bytes`: 系统独占消息的字节，缺少开头的`0xF0`或结尾的`0xF7`时会自动补上，中间不能含有其他状态字节，也不能为空。
+    /// - `frame_offset`: 相对于音频线程下一次处理开始时的帧偏移。
+    #[func]
+    fn send_sys_ex(&self, port: u16, bytes: PackedByteArray, frame_offset: u32) {
+        match self
+            .host
+            .check_note_dialect(port, NoteDialects::MIDI, "midi")
+            .and_then(|()| {
+                sys_ex_to_event(port, bytes.as_slice(), EventTime::FrameOffset(frame_offset))
+            }) {
+            Ok(event) => self
+                .host
+                .audio_access()
+                .send_scheduled_input_events(Box::new([event])),
+            Err(err) => godot_error!("发送系统独占消息失败，{err}"),
+        }
+    }
+
+    /// 向插件发送midi 2.0的UMP（通用midi数据包），可以用[`ClapUmp`](crate::clap_ump::ClapUmp)构造常用的数据包。
+    ///
+    /// 与midi 1.0相比，midi 2.0的力度有16位，控制器、逐音符控制器和逐音符弯音有32位的精度。
+    /// 只有支持midi 2.0方言的音符端口才能接收，见[`Self::get_note_port_dialects`]。
+    /// - `port`: 插件的输入音符端口下标。
+    /// - `words`: 一个或多个首尾相接的数据包，每个元素是一个32位的字，数据包的字数由其消息类型决定。
+    /// - `frame_offset`: 相对于音频线程下一次处理开始时的帧偏移。
+    #[func]
+    fn send_ump(&self, port: u16, words: PackedInt32Array, frame_offset: u32) {
+        if let Err(err) = self
+            .host
+            .check_note_dialect(port, NoteDialects::MIDI2, "midi 2.0")
+        {
+            godot_error!("发送UMP失败，{err}");
+            return;
+        }
+        let words = words
+            .as_slice()
+            .iter()
+            .map(|&word| word as u32)
+            .collect::<Vec<_>>();
+        match ump_to_events(port, &words, EventTime::FrameOffset(frame_offset)) {
+            Ok(events) => self
+                .host
+                .audio_access()
+                .send_scheduled_input_events(events.into()),
+            Err(err) => godot_error!("发送UMP失败，{err}"),
+        }
+    }
+
+    /// 插件的输入音符端口支持的音符方言，是`NOTE_DIALECT_*`常量的组合，端口不存在时为0。
+    #[func]
+    fn get_note_port_dialects(&self, port: u16) -> i64 {
+        self.host
+            .note_port_dialects(port)
+            .map_or(0, |dialects| dialects.bits() as i64)
+    }
+
+    /// 离线渲染，不依赖墙上时钟，以固定的块大小同步驱动插件处理，相同的输入总是得到相同的输出，可以用于测试和烘焙音频资源。
+    ///
+    /// 渲染前会重置插件，并丢弃尚未处理的音频和事件；渲染期间插件会被告知处于离线模式，
+    /// 渲染的音频不会发往各端口的访问句柄，插件输出的midi事件则照常通过`midi_received`信号发出。
+    /// - `frames`: 需要渲染的帧数，以插件的采样率计。
+    /// - `input_buffers`: 按端口顺序排列的所有输入通道的音频，缺少或不足的部分视为静音。
+    /// - `events`: 需要发送给插件的midi事件，每个事件是一个字典：
+    ///   `frame`为相对于渲染开始时的帧偏移，`midi`为[`InputEventMidi`]。
+    ///
+    /// 返回：按端口顺序排列的所有输出通道的音频，渲染失败时为空。
+    #[func]
+    fn render_offline(
+        &mut self,
+        frames: i64,
+        input_buffers: Array<PackedFloat32Array>,
+        events: Array<Dictionary>,
+    ) -> Array<PackedFloat32Array> {
+        let Ok(frames_count) = usize::try_from(frames) else {
+            godot_error!("离线渲染失败，帧数{frames}不合法");
+            return Array::new();
+        };
+        let input_channels = input_buffers
+            .iter_shared()
+            .map(|input_buffer| input_buffer.as_slice().into())
+            .collect();
+        let Some(output_channels) = self.render(frames_count, input_channels, &events) else {
+            return Array::new();
+        };
+        output_channels
+            .iter()
+            .map(|output_channel| PackedFloat32Array::from(&output_channel[..]))
+            .collect()
+    }
+
+    /// 离线渲染`seconds`秒插件的输出，并保存为[`AudioStreamWAV`]资源，可以用于将开销较大的音色预先渲染为采样。
+    ///
+    /// [`AudioStreamWAV`]最多只支持双声道，所以只会以16位保存前两个输出通道，需要完整的输出时请使用[`Self::bounce_to_wav_file`]。
+    /// - `seconds`: 需要渲染的时长。
+    /// - `input_stream`: 作为插件输入的音频流，其左右声道会被送往前两个输入通道，为空时输入静音。
+    /// - `events`: 需要发送给插件的midi事件，格式见[`Self::render_offline`]。
+    ///
+    /// 返回：渲染得到的音频，渲染失败时为空。
+    #[func]
+    fn bounce_to_audio_stream_wav(
+        &mut self,
+        seconds: f64,
+        input_stream: Option<Gd<AudioStream>>,
+        events: Array<Dictionary>,
+    ) -> Option<Gd<AudioStreamWAV>> {
+        let output_channels = self.bounce(seconds, input_stream, &events)?;
+        let output_channels = output_channels
+            .iter()
+            .take(2)
+            .map(|output_channel| &output_channel[..])
+            .collect_vec();
+
+        let mut audio_stream_wav = AudioStreamWAV::new_gd();
+        audio_stream_wav.set_format(audio_stream_wav::Format::FORMAT_16_BITS);
+        audio_stream_wav.set_mix_rate(self.host.sample_rate().round() as i32);
+        audio_stream_wav.set_stereo(output_channels.len() == 2);
+        audio_stream_wav.set_data(&PackedByteArray::from(encode_interleaved(
+            WavSampleFormat::Pcm16,
+            &output_channels,
+        )));
+        Some(audio_stream_wav)
+    }
+
+    /// 离线渲染`seconds`秒插件的输出，并将所有输出通道按端口顺序保存为WAV文件。
+    /// - `path`: 保存的路径，可以使用`res://`、`user://`等Godot路径。
+    /// - `bits_per_sample`: 每个采样的位数，`16`、`24`为整数，`32`为浮点数。
+    /// - `seconds`、`input_stream`、`events`: 见[`Self::bounce_to_audio_stream_wav`]。
+    ///
+    /// 返回：是否保存成功。
+    #[func]
+    fn bounce_to_wav_file(
+        &mut self,
+        path: GString,
+        bits_per_sample: i64,
+        seconds: f64,
+        input_stream: Option<Gd<AudioStream>>,
+        events: Array<Dictionary>,
+    ) -> bool {
+        let Some(sample_format) = WavSampleFormat::from_bits_per_sample(bits_per_sample) else {
+            godot_error!("保存WAV文件失败，不支持{bits_per_sample}位的采样，只支持16、24、32位");
+            return false;
+        };
+        let Some(output_channels) = self.bounce(seconds, input_stream, &events) else {
+            return false;
+        };
+        let output_channels = output_channels
+            .iter()
+            .map(|output_channel| &output_channel[..])
+            .collect_vec();
+
+        let path = ProjectSettings::singleton()
+            .globalize_path(&path)
+            .to_string();
+        let write_result = File::create(&path).and_then(|file| {
+            let mut wav_writer = WavWriter::new(
+                BufWriter::new(file),
+                sample_format,
+                output_channels.len() as u16,
+                self.host.sample_rate().round() as u32,
+            )?;
+            wav_writer.write_channels(&output_channels)?;
+            wav_writer.finish()?;
+            Ok(())
+        });
+        if let Err(err) = write_result {
+            godot_error!("保存WAV文件“{path}”失败：{err}");
+            return false;
+        }
+        true
+    }
+
     /// 收到来自插件的midi事件。
     #[signal]
     fn midi_received(midi: Array<Gd<InputEventMidi>>);
 
+    /// 收到来自插件的原始midi数据，除系统独占消息外，这些数据同时也会经`midi_received`发出。
+    /// - `port`: 插件的音符端口下标。
+    /// - `bytes`: 一条完整的midi消息，系统独占消息包括开头的`0xF0`和结尾的`0xF7`。
+    /// - `frame`: 消息发生时插件的稳定时间，见[`Self::get_steady_time`]。
+    #[signal]
+    fn midi_bytes_received(port: u16, bytes: PackedByteArray, frame: u64);
+
+    /// 收到来自插件的midi 2.0数据包，这些数据包不会经`midi_received`发出。
+    /// - `port`: 插件的输出音符端口下标。
+    /// - `words`: 一个完整的UMP数据包，格式见[`Self::send_ump`]。
+    /// - `frame`: 数据包发生时插件的稳定时间，见[`Self::get_steady_time`]。
+    #[signal]
+    fn ump_received(port: u16, words: PackedInt32Array, frame: u64);
+
+    /// 参数的值发生了变化。
+    #[constant]
+    const PARAM_RESCAN_VALUES: i64 = ParamRescanFlags::VALUES.bits() as i64;
+    /// 参数值转换为文本的方式发生了变化。
+    #[constant]
+    const PARAM_RESCAN_TEXT: i64 = ParamRescanFlags::TEXT.bits() as i64;
+    /// 参数的描述信息发生了变化，但参数列表没有变化。
+    #[constant]
+    const PARAM_RESCAN_INFO: i64 = ParamRescanFlags::INFO.bits() as i64;
+    /// 参数列表发生了变化，需要重新获取所有参数的描述信息。
+    #[constant]
+    const PARAM_RESCAN_ALL: i64 = ParamRescanFlags::ALL.bits() as i64;
+
+    /// 音符端口支持Clap的音符事件。
+    #[constant]
+    const NOTE_DIALECT_CLAP: i64 = NoteDialects::CLAP.bits() as i64;
+    /// 音符端口支持原始的midi 1.0数据。
+    #[constant]
+    const NOTE_DIALECT_MIDI: i64 = NoteDialects::MIDI.bits() as i64;
+    /// 音符端口支持MPE（复音表情midi）。
+    #[constant]
+    const NOTE_DIALECT_MIDI_MPE: i64 = NoteDialects::MIDI_MPE.bits() as i64;
+    /// 音符端口支持midi 2.0的UMP。
+    #[constant]
+    const NOTE_DIALECT_MIDI2: i64 = NoteDialects::MIDI2.bits() as i64;
+
+    /// 插件通知参数发生了变化，缓存的参数描述信息和参数值已经被刷新。
+    /// - `flags`: 发生变化的内容，可以和本类的`PARAM_RESCAN_*`常量做按位与运算来判断。
+    #[signal]
+    fn params_rescanned(flags: u32);
+
+    /// 插件侧修改了参数的值，通常是用户在插件GUI上操作了参数。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `value`: 参数的新值。
+    #[signal]
+    fn param_changed(param_id: u32, value: f64);
+
+    /// 用户开始在插件GUI上调整参数，直到[`Self::param_gesture_end`]之前的参数变化都属于同一次操作，可用于合并撤销记录。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    #[signal]
+    fn param_gesture_begin(param_id: u32);
+
+    /// 用户结束了在插件GUI上对参数的调整。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    #[signal]
+    fn param_gesture_end(param_id: u32);
+
+    /// 获取插件的所有参数的描述信息。插件不支持参数功能时返回空数组。
+    #[func]
+    fn param_infos(&self) -> Array<Gd<ClapParamInfo>> {
+        self.host
+            .param_access()
+            .param_infos()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// 获取插件参数的当前值。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    ///
+    /// 返回：参数的当前值，获取失败时返回`NAN`。
+    #[func]
+    fn get_param_value(&mut self, param_id: u32) -> f64 {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("获取插件参数失败，参数编号{param_id}不合法");
+            return f64::NAN;
+        };
+        self.host.get_param_value(param_id).unwrap_or_else(|| {
+            godot_error!(
+                "获取插件参数失败，插件没有给出编号为{}的参数的值",
+                param_id.get()
+            );
+            f64::NAN
+        })
+    }
+
+    /// 设置插件参数的值，超出参数取值范围的值会被限制在范围内。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `value`: 参数的新值，会随下一次音频处理送达插件，所以紧接着调用[`Self::get_param_value`]可能还是旧值。
+    #[func]
+    fn set_param_value(&mut self, param_id: u32, value: f64) {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("设置插件参数失败，参数编号{param_id}不合法");
+            return;
+        };
+        self.host.set_param_value(param_id, value);
+    }
+
+    /// 让插件将参数值转换为用于显示的文本，例如`-12.0 dB`或`Sawtooth`。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `value`: 需要转换的参数值，不一定是参数的当前值。
+    ///
+    /// 返回：转换后的文本，插件不支持转换时返回空字符串。
+    #[func]
+    fn param_value_to_text(&mut self, param_id: u32, value: f64) -> GString {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("转换插件参数值失败，参数编号{param_id}不合法");
+            return GString::new();
+        };
+        self.host
+            .param_value_to_text(param_id, value)
+            .map(|text| text.to_godot())
+            .unwrap_or_else(|| {
+                godot_error!(
+                    "转换插件参数值失败，插件无法将编号为{}的参数的值{value}转换为文本",
+                    param_id.get()
+                );
+                GString::new()
+            })
+    }
+
+    /// 让插件将文本解析为参数值，可用于让玩家直接输入参数值。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `text`: 需要解析的文本，例如`-12 dB`。
+    ///
+    /// 返回：解析出的参数值，解析失败时返回`NAN`。
+    #[func]
+    fn param_text_to_value(&mut self, param_id: u32, text: GString) -> f64 {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("解析插件参数值失败，参数编号{param_id}不合法");
+            return f64::NAN;
+        };
+        self.host
+            .param_text_to_value(param_id, &text.to_string())
+            .unwrap_or_else(|| {
+                godot_error!(
+                    "解析插件参数值失败，插件无法将文本“{text}”解析为编号为{}的参数的值",
+                    param_id.get()
+                );
+                f64::NAN
+            })
+    }
+
+    /// 在音频线程下一次处理开始后的第`frame_offset`帧设置插件参数的值，用于实现采样级精确的参数自动化。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `value`: 参数的新值。
+    /// - `frame_offset`: 相对于音频线程下一次处理开始时的帧偏移。
+    #[func]
+    fn schedule_param_value(&mut self, param_id: u32, value: f64, frame_offset: u32) {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("设置插件参数失败，参数编号{param_id}不合法");
+            return;
+        };
+        self.host
+            .schedule_param_value(param_id, value, EventTime::FrameOffset(frame_offset));
+    }
+
+    /// 在插件的稳定时间到达`steady_time`时设置插件参数的值，用于实现采样级精确的参数自动化。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `value`: 参数的新值。
+    /// - `steady_time`: 参数生效时插件的稳定时间，见[`Self::get_steady_time`]。已经过去的时间会在下一次处理开始时立即生效。
+    #[func]
+    fn schedule_param_value_at_steady_time(&mut self, param_id: u32, value: f64, steady_time: u64) {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("设置插件参数失败，参数编号{param_id}不合法");
+            return;
+        };
+        self.host
+            .schedule_param_value(param_id, value, EventTime::SteadyTime(steady_time));
+    }
+
+    /// 设置插件参数的全局调制量。
+    ///
+    /// 调制量是叠加在参数值之上的非破坏性偏移，不会改变参数本身的值，也不会被保存到[`Self::state`]中，适合用于由游戏逻辑驱动的LFO等效果。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]，参数需要支持调制（见[`ClapParamInfo::IS_MODULATABLE`]）。
+    /// - `amount`: 调制量，以参数值为单位，设为`0.0`即可取消调制。
+    #[func]
+    fn send_param_mod(&mut self, param_id: u32, amount: f64) {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("设置插件参数调制量失败，参数编号{param_id}不合法");
+            return;
+        };
+        self.host.schedule_param_mod(
+            param_id,
+            amount,
+            Pckn::match_all(),
+            EventTime::FrameOffset(0),
+        );
+    }
+
+    /// 设置插件参数对指定音符的调制量，参见[`Self::send_param_mod`]。
+    /// - `param_id`: 参数编号，见[`ClapParamInfo`]。
+    /// - `amount`: 调制量，以参数值为单位。
+    /// - `port_index`、`channel`、`key`、`note_id`: 调制作用的音符端口、通道、音高和音符编号，为负数时表示匹配所有。
+    #[func]
+    fn send_param_mod_per_note(
+        &mut self,
+        param_id: u32,
+        amount: f64,
+        port_index: i32,
+        channel: i32,
+        key: i32,
+        note_id: i32,
+    ) {
+        let Some(param_id) = ClapId::from_raw(param_id) else {
+            godot_error!("设置插件参数调制量失败，参数编号{param_id}不合法");
+            return;
+        };
+        self.host.schedule_param_mod(
+            param_id,
+            amount,
+            pckn_from_godot(port_index, channel, key, note_id),
+            EventTime::FrameOffset(0),
+        );
+    }
+
+    /// 插件的延迟帧数，即插件的输出比输入晚了多少帧，以插件的采样率计，插件不支持延迟扩展时为0。
+    ///
+    /// 例如前视压缩器需要先看到一段输入才能输出，需要和画面同步时可以据此推算音频实际落后的时间。
+    #[func]
+    fn get_latency_frames(&self) -> u32 {
+        self.host.latency_frames()
+    }
+
+    /// 插件的延迟发生了变化。
+    /// - `latency_frames`: 新的延迟帧数，见[`Self::get_latency_frames`]。
+    #[signal]
+    fn latency_changed(latency_frames: u32);
+
+    #[func]
+    fn is_sleeping(&self) -> bool {
+        self.host.audio_access().is_sleeping()
+    }
+
+    /// 插件从睡眠中醒来，重新开始处理，见[`Self::is_sleeping`]。
+    #[signal]
+    fn woke_up();
+
+    /// 插件的稳定时间，即音频线程自插件开始处理起已处理的总帧数，以插件的采样率计。
+    #[func]
+    fn get_steady_time(&self) -> u64 {
+        self.host.audio_access().steady_time()
+    }
+
     #[func]
     fn get_clap_transport_event_access(&self) -> Option<Gd<ClapTransportEventAccess>> {
         self.host
@@ -281,22 +1041,63 @@ impl INode for ClapPluginInstance {
             }
             NodeNotification::INTERNAL_PROCESS => {
                 // 之所以写在这里而不是 process 方法，是因为 on_notification 方法被子类重写之后依然会被正常调用，而 process 等其他方法被重写之后就会被覆盖掉。
-                self.host.process();
-
-                // 插件midi事件触发。
-                let midi_received = self
-                    .host
-                    .audio_access()
-                    .output_event_buffer_rx()
-                    .try_iter()
-                    .flatten()
-                    .map(event_to_midi)
-                    .collect::<Array<_>>();
-                if !midi_received.is_empty() {
-                    self.signals().midi_received().emit(&midi_received);
+                for host_event in self.host.process() {
+                    self.process_host_event(host_event);
                 }
+
+                self.process_output_events();
             }
             _ => {}
         }
     }
+
+    fn get_property_list(&mut self) -> Vec<PropertyInfo> {
+        self.host
+            .param_access()
+            .param_infos()
+            .iter()
+            .filter_map(|param_info| param_info.bind().property_info())
+            .collect()
+    }
+
+    fn get_property(&self, property: StringName) -> Option<Variant> {
+        let param_access = self.host.param_access();
+        let param_info = param_access.param_info_by_property_name(&property)?;
+        let param_id = ClapId::from_raw(param_info.bind().id())?;
+        param_access
+            .cached_value(param_id)
+            .map(|value| value.to_variant())
+    }
+
+    fn set_property(&mut self, property: StringName, value: Variant) -> bool {
+        let Some(param_info) = self
+            .host
+            .param_access()
+            .param_info_by_property_name(&property)
+            .map(|param_info| param_info.bind())
+        else {
+            return false;
+        };
+        if param_info.is_readonly() {
+            godot_error!("设置插件参数属性{property}失败，该参数是只读的");
+            return true;
+        }
+        let Some(param_id) = ClapId::from_raw(param_info.id()) else {
+            return false;
+        };
+        match value.try_to::<f64>() {
+            Ok(value) => self.host.set_param_value(param_id, value),
+            Err(convert_error) => {
+                godot_error!("设置插件参数属性{property}失败，值无法转换为浮点数：{convert_error}")
+            }
+        }
+        true
+    }
+
+    fn property_get_revert(&self, property: StringName) -> Option<Variant> {
+        self.host
+            .param_access()
+            .param_info_by_property_name(&property)
+            .map(|param_info| param_info.bind().default_value().to_variant())
+    }
 }
@@ -0,0 +1,150 @@
+use godot::prelude::*;
+
+/// midi 2.0通道声音消息的UMP消息类型。
+const MIDI2_CHANNEL_VOICE_MESSAGE_TYPE: u32 = 0x4;
+
+/// 用于构造常用的midi 2.0 UMP（通用midi数据包）的工具类，构造出的数据包可以交给`ClapPluginInstance`的`send_ump`发送。
+///
+/// 所有的值都以浮点数给出，再换算为midi 2.0的精度：力度为16位，控制器和弯音为32位。
+/// `group`和`channel`只取低4位，`note`和控制变化的`index`只取低7位，逐音符控制器的`index`则是完整的8位。
+#[derive(GodotClass)]
+#[class(no_init, base = RefCounted)]
+pub struct ClapUmp {}
+#[godot_api]
+impl ClapUmp {
+    /// 音符开。
+    /// - `velocity`: 力度，取值范围为`[0.0, 1.0]`。
+    #[func]
+    fn note_on(group: u8, channel: u8, note: u8, velocity: f64) -> PackedInt32Array {
+        channel_voice(group, 0x9, channel, note, 0, to_u16(velocity) << 16)
+    }
+
+    /// 音符关。
+    /// - `velocity`: 释放力度，取值范围为`[0.0, 1.0]`。
+    #[func]
+    fn note_off(group: u8, channel: u8, note: u8, velocity: f64) -> PackedInt32Array {
+        channel_voice(group, 0x8, channel, note, 0, to_u16(velocity) << 16)
+    }
+
+    /// 逐音符控制器，只作用于`note`这一个音符。
+    /// - `index`: 控制器编号，取值范围为`[0, 255]`。
+    /// - `value`: 控制器的值，取值范围为`[0.0, 1.0]`。
+    /// - `registered`: 是否为注册的控制器（由midi协会定义含义），否则为可由插件自行分配的控制器。
+    #[func]
+    fn per_note_controller(
+        group: u8,
+        channel: u8,
+        note: u8,
+        index: u8,
+        value: f64,
+        registered: bool,
+    ) -> PackedInt32Array {
+        let status = if registered { 0x0 } else { 0x1 };
+        channel_voice(group, status, channel, note, index, to_u32(value))
+    }
+
+    /// 逐音符弯音，只作用于`note`这一个音符。
+    /// - `bend`: 弯音量，取值范围为`[-1.0, 1.0]`，`0.0`为不弯音。
+    #[func]
+    fn per_note_pitch_bend(group: u8, channel: u8, note: u8, bend: f64) -> PackedInt32Array {
+        channel_voice(group, 0x6, channel, note, 0, bend_to_u32(bend))
+    }
+
+    /// 控制变化。
+    /// - `index`: 控制器编号。
+    /// - `value`: 控制器的值，取值范围为`[0.0, 1.0]`。
+    #[func]
+    fn control_change(group: u8, channel: u8, index: u8, value: f64) -> PackedInt32Array {
+        channel_voice(group, 0xB, channel, index, 0, to_u32(value))
+    }
+
+    /// 作用于整个通道的弯音。
+    /// - `bend`: 弯音量，取值范围为`[-1.0, 1.0]`，`0.0`为不弯音。
+    #[func]
+    fn pitch_bend(group: u8, channel: u8, bend: f64) -> PackedInt32Array {
+        channel_voice(group, 0xE, channel, 0, 0, bend_to_u32(bend))
+    }
+}
+
+/// 构造一个64位的midi 2.0通道声音消息。
+/// - `data1`: 第一个字的第三个字节，例如音符编号，只取低7位。
+/// - `data2`: 第一个字的最低字节，例如逐音符控制器的编号。
+/// - `data`: 第二个字。
+fn channel_voice(
+    group: u8,
+    status: u32,
+    channel: u8,
+    data1: u8,
+    data2: u8,
+    data: u32,
+) -> PackedInt32Array {
+    PackedInt32Array::from(
+        channel_voice_words(group, status, channel, data1, data2, data).map(|word| word as i32),
+    )
+}
+
+/// 见[`channel_voice`]。
+fn channel_voice_words(
+    group: u8,
+    status: u32,
+    channel: u8,
+    data1: u8,
+    data2: u8,
+    data: u32,
+) -> [u32; 2] {
+    let first_word = MIDI2_CHANNEL_VOICE_MESSAGE_TYPE << 28
+        | (group as u32 & 0x0F) << 24
+        | status << 20
+        | (channel as u32 & 0x0F) << 16
+        | (data1 as u32 & 0x7F) << 8
+        | data2 as u32;
+    [first_word, data]
+}
+
+fn to_u16(value: f64) -> u32 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f64).round() as u32
+}
+
+fn to_u32(value: f64) -> u32 {
+    (value.clamp(0.0, 1.0) * u32::MAX as f64).round() as u32
+}
+
+/// 将`[-1.0, 1.0]`的弯音量换算为以`0x80000000`为中心的32位值。
+fn bend_to_u32(bend: f64) -> u32 {
+    to_u32((bend + 1.0) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_voice_bit_packing() {
+        assert_eq!(
+            channel_voice_words(0x1, 0x9, 0x2, 60, 0, to_u16(1.0) << 16),
+            [0x4192_3C00, 0xFFFF_0000]
+        );
+        // 超出位宽的部分会被截掉，`data2`则保留完整的8位。
+        assert_eq!(
+            channel_voice_words(0x1F, 0xB, 0x1F, 0xFF, 0xFF, 0),
+            [0x4FBF_7FFF, 0]
+        );
+        // 编号超过127的逐音符控制器不会和其他控制器混淆。
+        assert_eq!(
+            channel_voice_words(0, 0x1, 0, 60, 200, u32::MAX),
+            [0x4010_3CC8, u32::MAX]
+        );
+    }
+
+    #[test]
+    fn value_scaling() {
+        assert_eq!(to_u16(0.0), 0);
+        assert_eq!(to_u16(1.0), 0xFFFF);
+        assert_eq!(to_u16(2.0), 0xFFFF);
+        assert_eq!(to_u32(-1.0), 0);
+        assert_eq!(to_u32(1.0), u32::MAX);
+        assert_eq!(bend_to_u32(-1.0), 0);
+        assert_eq!(bend_to_u32(0.0), 0x8000_0000);
+        assert_eq!(bend_to_u32(1.0), u32::MAX);
+    }
+}
@@ -2,34 +2,62 @@ mod audio_access;
 mod audio_processor;
 pub mod host_handlers_impl;
 mod message_processor;
+mod param_access;
+mod plugin_bundle_cache;
 mod plugin_message;
 
+pub use audio_access::OutputEvent;
+pub use audio_processor::{
+    SharedAudioProcessor,
+    event_buffer::{EventTime, ScheduledEvent},
+};
+pub use plugin_bundle_cache::load_plugin_bundle;
+
 use crate::{
+    clap_plugin_descriptor::ClapPluginDescriptor,
     clap_transport_event_access::ClapTransportEventAccess,
     host::{
         audio_access::AudioAccess,
         audio_processor::AudioProcessor,
         host_handlers_impl::{host_main_thread::HostMainThread, host_shared::HostShared},
         message_processor::{MessageProcessor, message_processor_impl::MessageProcessorImpl},
-        plugin_message::PluginMessage,
+        param_access::ParamAccess,
+        plugin_message::{PluginMessage, PluginParamsMessage},
     },
+    midi::{MidiConversionError, midi_to_event, to_unknown},
+};
+use clack_extensions::{
+    latency::PluginLatency,
+    log::{HostLogImpl, LogSeverity},
+    note_ports::{NoteDialect, NoteDialects, NotePortInfoBuffer, PluginNotePorts},
+    params::ParamRescanFlags,
+    render::{PluginRender, RenderMode},
+    state::PluginState,
 };
-use clack_extensions::state::PluginState;
 use clack_host::{
     bundle::{PluginBundle, PluginBundleError},
+    events::{
+        Pckn, UnknownEvent,
+        event_types::{ParamModEvent, ParamValueEvent},
+        spaces::CoreEventSpace,
+    },
     factory::PluginDescriptor,
     host::{HostHandlers, HostInfo},
     plugin::{PluginInstance, PluginInstanceError},
+    prelude::{InputEvents, OutputEvents},
     process::ProcessingStartError,
+    utils::{ClapId, Cookie},
 };
-use godot::prelude::*;
+use godot::{classes::InputEventMidi, prelude::*};
 use std::{
+    any::Any,
     error::Error,
     fmt::Display,
+    io,
     ops::Deref,
     path::{Path, PathBuf},
     sync::{
-        LazyLock,
+        Arc, LazyLock,
         mpsc::{Receiver, channel},
     },
     thread::{JoinHandle, spawn},
@@ -40,39 +68,72 @@ pub struct Host {
     /// [`插件消息`](PluginMessage)处理器
     message_processor: MessageProcessor<Self>,
 
-    /// 音频线程
-    audio_processor_thread: Option<JoinHandle<()>>,
+    /// 音频处理的驱动，插件已被停用时为空
+    audio_driver: Option<AudioDriver>,
     /// 音频线程访问句柄
     audio_access: AudioAccess,
+    /// 插件参数访问句柄
+    param_access: ParamAccess,
+    /// 插件描述信息
+    plugin_descriptor: Gd<ClapPluginDescriptor>,
+    /// 插件处理音频时使用的采样率
+    sample_rate: f64,
+    /// 插件的延迟帧数，插件不支持延迟扩展时为0
+    latency_frames: u32,
+    /// 插件各个输入音符端口支持的音符方言，插件不支持音符端口扩展时为空
+    note_port_dialects: Box<[NotePortDialects]>,
+    /// 插件所在的clap文件在[`load_plugin_bundle`]缓存中的强引用，由使用同一个clap文件的所有主机共享。
+    /// 插件实例自己持有clap文件的克隆，不靠这里保活；这里只是让缓存在主机存活期间保持有效，
+    /// 以便其他主机复用已加载的clap文件，而不是重新加载并再次初始化插件入口。
+    #[allow(unused)]
+    plugin_bundle: Arc<PluginBundle>,
 }
 impl Host {
     fn try_new(
         plugin_rx: Receiver<PluginMessage>,
         mut plugin_instance: PluginInstance<Self>,
+        plugin_descriptor: Gd<ClapPluginDescriptor>,
+        plugin_bundle: Arc<PluginBundle>,
         sample_rate: f64,
         max_latency_seconds: f64,
+        audio_driver_mode: AudioDriverMode,
     ) -> Result<Self, HostBuildError> {
-        let (mut audio_processor, audio_access) =
+        let (audio_processor, audio_access) =
             AudioProcessor::try_new(&mut plugin_instance, sample_rate, max_latency_seconds)?;
+        let host_shared = plugin_instance.access_shared_handler(|host_shared| host_shared.clone());
+        let param_access = ParamAccess::new(&host_shared, &mut plugin_instance.plugin_handle());
         let message_processor = MessageProcessor::<Host>::new(plugin_rx, plugin_instance);
 
-        let audio_processor_thread = Some(spawn(move || {
-            loop {
-                audio_processor.process();
+        let audio_driver = Some(match audio_driver_mode {
+            AudioDriverMode::Thread => AudioDriver::Thread(spawn(move || audio_processor.run())),
+            AudioDriverMode::External => {
+                AudioDriver::External(SharedAudioProcessor::new(audio_processor))
             }
-        }));
+        });
 
-        Ok(Self {
+        let mut host = Self {
             message_processor,
-            audio_processor_thread,
+            audio_driver,
             audio_access,
-        })
+            param_access,
+            plugin_descriptor,
+            sample_rate,
+            latency_frames: 0,
+            note_port_dialects: Box::new([]),
+            plugin_bundle,
+        };
+        // Clap规定插件的延迟只能在激活之后查询。
+        host.latency_frames = host.query_latency_frames();
+        host.note_port_dialects = host.query_note_port_dialects();
+        Ok(host)
     }
     fn try_new_from_plugin_descriptor(
-        plugin_bundle: &PluginBundle,
+        path: &Path,
+        plugin_bundle: &Arc<PluginBundle>,
         plugin_descriptor: PluginDescriptor,
         sample_rate: f64,
         max_latency_seconds: f64,
+        audio_driver_mode: AudioDriverMode,
     ) -> Result<Self, HostBuildError> {
         let plugin_id =
             plugin_descriptor
@@ -80,6 +141,11 @@ impl Host {
                 .ok_or(HostBuildError::InvalidePluginDescriptorMemberValue {
                     member_name: "id".to_string(),
                 })?;
+        let clap_plugin_descriptor = ClapPluginDescriptor::new(path, &plugin_descriptor).ok_or(
+            HostBuildError::InvalidePluginDescriptorMemberValue {
+                member_name: "id".to_string(),
+            },
+        )?;
         let (plugin_tx, plugin_rx) = channel();
         let plugin_instance = PluginInstance::<Self>::new(
             |()| HostShared::new(plugin_tx),
@@ -90,19 +156,22 @@ impl Host {
                 .as_ref()
                 .ok_or(HostBuildError::HostInfoBuildFailed)?,
         )?;
-        Self::try_new(plugin_rx, plugin_instance, sample_rate, max_latency_seconds)
+        Self::try_new(
+            plugin_rx,
+            plugin_instance,
+            clap_plugin_descriptor,
+            plugin_bundle.clone(),
+            sample_rate,
+            max_latency_seconds,
+            audio_driver_mode,
+        )
     }
     pub fn try_new_from_clap_file(
         path: &Path,
         sample_rate: f64,
         max_latency_seconds: f64,
     ) -> Result<Box<[Result<Self, HostBuildError>]>, ClapFileError> {
-        let plugin_bundle = unsafe { PluginBundle::load(path) }.map_err(|err| {
-            ClapFileError::PluginBundleError {
-                path: path.to_path_buf(),
-                plugin_bundle_error: err,
-            }
-        })?;
+        let plugin_bundle = load_plugin_bundle(path)?;
         let plugin_fatory =
             plugin_bundle
                 .get_plugin_factory()
@@ -113,21 +182,170 @@ impl Host {
             .into_iter()
             .map(|plugin_descriptor| {
                 Self::try_new_from_plugin_descriptor(
+                    path,
                     &plugin_bundle,
                     plugin_descriptor,
                     sample_rate,
                     max_latency_seconds,
+                    AudioDriverMode::Thread,
                 )
             })
             .collect();
         Ok(hosts)
     }
+    /// 只实例化clap文件中编号为`plugin_id`的插件，不会实例化文件中的其他插件。
+    pub fn try_new_from_clap_file_with_id(
+        path: &Path,
+        plugin_id: &str,
+        sample_rate: f64,
+        max_latency_seconds: f64,
+        audio_driver_mode: AudioDriverMode,
+    ) -> Result<Result<Self, HostBuildError>, ClapFileError> {
+        let plugin_bundle = load_plugin_bundle(path)?;
+        let plugin_fatory =
+            plugin_bundle
+                .get_plugin_factory()
+                .ok_or(ClapFileError::NoPluginFactory {
+                    path: path.to_path_buf(),
+                })?;
+        let plugin_descriptor = plugin_fatory
+            .into_iter()
+            .find(|plugin_descriptor| {
+                plugin_descriptor
+                    .id()
+                    .is_some_and(|id| id.to_bytes() == plugin_id.as_bytes())
+            })
+            .ok_or_else(|| ClapFileError::PluginIdNotFound {
+                path: path.to_path_buf(),
+                plugin_id: plugin_id.to_string(),
+            })?;
+        Ok(Self::try_new_from_plugin_descriptor(
+            path,
+            &plugin_bundle,
+            plugin_descriptor,
+            sample_rate,
+            max_latency_seconds,
+            audio_driver_mode,
+        ))
+    }
 
     /// 插件实例，兼插件消息处理器
     pub fn message_processor(&self) -> &dyn MessageProcessorImpl<Self> {
         self.message_processor.deref()
     }
 
+    /// 插件描述信息
+    pub fn plugin_descriptor(&self) -> &Gd<ClapPluginDescriptor> {
+        &self.plugin_descriptor
+    }
+
+    /// 插件处理音频时使用的采样率
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// 插件的延迟帧数，即插件的输出比输入晚了多少帧，以插件的采样率计。
+    pub fn latency_frames(&self) -> u32 {
+        self.latency_frames
+    }
+
+    /// 向插件查询延迟帧数，插件不支持延迟扩展时为0。
+    fn query_latency_frames(&mut self) -> u32 {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        plugin_main_thread_handle
+            .get_extension::<PluginLatency>()
+            .map_or(0, |plugin_latency| {
+                plugin_latency.get(&mut plugin_main_thread_handle)
+            })
+    }
+
+    /// 插件的输入音符端口支持的音符方言。
+    ///
+    /// 返回：端口不存在时为空。
+    pub fn note_port_dialects(&self, port_index: u16) -> Option<NoteDialects> {
+        self.note_port_dialects
+            .get(port_index as usize)
+            .map(|note_port_dialects| note_port_dialects.supported)
+    }
+
+    /// 检查插件的输入音符端口是否支持音符方言`dialect`。
+    /// - `dialect_name`: 出错时用于说明的方言名称。
+    pub fn check_note_dialect(
+        &self,
+        port_index: u16,
+        dialect: NoteDialects,
+        dialect_name: &'static str,
+    ) -> Result<(), MidiConversionError> {
+        let supported = self
+            .note_port_dialects(port_index)
+            .ok_or(MidiConversionError::NoNotePort(port_index))?;
+        if !supported.contains(dialect) {
+            return Err(MidiConversionError::UnsupportedDialect {
+                port_index,
+                dialect: dialect_name,
+            });
+        }
+        Ok(())
+    }
+
+    /// 按插件输入音符端口协商的音符方言，将Godot的midi事件转换为Clap事件，见[`NotePortDialects::midi_dialect`]。
+    pub fn midi_to_event(
+        &self,
+        midi: Gd<InputEventMidi>,
+    ) -> Result<Box<UnknownEvent>, MidiConversionError> {
+        let port_index =
+            u16::try_from(midi.get_device()).map_err(|_| MidiConversionError::ValueOutOfRange {
+                name: "device",
+                value: midi.get_device(),
+                max: u16::MAX as i32,
+            })?;
+        let note_port_dialects = self
+            .note_port_dialects
+            .get(port_index as usize)
+            .ok_or(MidiConversionError::NoNotePort(port_index))?;
+        let note_dialect =
+            note_port_dialects
+                .midi_dialect()
+                .ok_or(MidiConversionError::UnsupportedDialect {
+                    port_index,
+                    dialect: "Clap或midi",
+                })?;
+        let event = midi_to_event(midi, note_dialect)?;
+        // 没有对应Clap核心事件的消息只能以midi方言发送。
+        if matches!(event.as_core_event(), Some(CoreEventSpace::Midi(_))) {
+            self.check_note_dialect(port_index, NoteDialects::MIDI, "midi")?;
+        }
+        Ok(event)
+    }
+
+    /// 向插件查询各个输入音符端口支持的音符方言。
+    fn query_note_port_dialects(&mut self) -> Box<[NotePortDialects]> {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        let Some(plugin_note_ports) = plugin_main_thread_handle.get_extension::<PluginNotePorts>()
+        else {
+            return Box::new([]);
+        };
+        let mut buffer = NotePortInfoBuffer::new();
+        (0..plugin_note_ports.count(&mut plugin_main_thread_handle, true))
+            .map(|index| {
+                plugin_note_ports
+                    .get(&mut plugin_main_thread_handle, index, true, &mut buffer)
+                    .map_or(
+                        NotePortDialects {
+                            supported: NoteDialects::empty(),
+                            preferred: None,
+                        },
+                        |note_port_info| NotePortDialects {
+                            supported: note_port_info.supported_dialects,
+                            preferred: note_port_info.preferred_dialect,
+                        },
+                    )
+            })
+            .collect()
+    }
+
     /// 获取插件的持久化状态
     pub fn get_state(&mut self) -> Box<[u8]> {
         let mut plugin_main_thread_handle =
@@ -156,11 +374,110 @@ impl Host {
             return;
         };
 
-        plugin_state
-            .load(&mut plugin_main_thread_handle, &mut state)
-            .unwrap_or_else(|err| {
-                godot_error!("设置插件的持久化状态失败：{err}");
-            });
+        match plugin_state.load(&mut plugin_main_thread_handle, &mut state) {
+            // 恢复状态会改变参数值，需要同步参数值的缓存。
+            Ok(()) => self
+                .param_access
+                .refresh_values(&mut plugin_main_thread_handle),
+            Err(err) => godot_error!("设置插件的持久化状态失败：{err}"),
+        }
+    }
+
+    /// 插件参数访问句柄。
+    pub fn param_access(&self) -> &ParamAccess {
+        &self.param_access
+    }
+
+    /// 获取插件参数的当前值
+    pub fn get_param_value(&mut self, param_id: ClapId) -> Option<f64> {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        self.param_access
+            .get_value(&mut plugin_main_thread_handle, param_id)
+    }
+
+    /// 让插件将参数值转换为用于显示的文本。
+    pub fn param_value_to_text(&mut self, param_id: ClapId, value: f64) -> Option<String> {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        self.param_access
+            .value_to_text(&mut plugin_main_thread_handle, param_id, value)
+    }
+
+    /// 让插件将文本解析为参数值。
+    pub fn param_text_to_value(&mut self, param_id: ClapId, text: &str) -> Option<f64> {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        self.param_access
+            .text_to_value(&mut plugin_main_thread_handle, param_id, text)
+    }
+
+    /// 设置插件参数的值，新值会以事件的形式随下一次音频处理送达插件。
+    pub fn set_param_value(&self, param_id: ClapId, value: f64) {
+        self.schedule_param_value(param_id, value, EventTime::FrameOffset(0));
+    }
+
+    /// 在指定的时间设置插件参数的值，音频线程会在对应的帧上把新值送达插件。只读的参数无法设置。
+    pub fn schedule_param_value(&self, param_id: ClapId, value: f64, event_time: EventTime) {
+        let Some(param_info) = self.param_access.param_info(param_id) else {
+            godot_error!("设置插件参数失败，找不到编号为{}的参数", param_id.get());
+            return;
+        };
+        let param_info = param_info.bind();
+        if param_info.is_readonly() {
+            godot_error!("设置插件参数失败，编号为{}的参数是只读的", param_id.get());
+            return;
+        }
+        let value = param_info.clamp_value(value);
+        self.param_access.set_cached_value(param_id, value);
+        self.audio_access
+            .send_scheduled_input_events(Box::new([ScheduledEvent::new(
+                event_time,
+                to_unknown(ParamValueEvent::new(
+                    0,
+                    param_id,
+                    Pckn::match_all(),
+                    value,
+                    Cookie::empty(),
+                )),
+            )]));
+    }
+
+    /// 在指定的时间设置插件参数的调制量。
+    ///
+    /// 调制量是叠加在参数值之上的非破坏性偏移，不会改变参数本身的值，也就不会被保存到插件的持久化状态中。
+    /// - `pckn`: 调制作用的音符范围，全部匹配时为全局调制。
+    pub fn schedule_param_mod(
+        &self,
+        param_id: ClapId,
+        amount: f64,
+        pckn: Pckn,
+        event_time: EventTime,
+    ) {
+        let Some(param_info) = self.param_access.param_info(param_id) else {
+            godot_error!(
+                "设置插件参数调制量失败，找不到编号为{}的参数",
+                param_id.get()
+            );
+            return;
+        };
+        if !param_info.bind().is_modulatable() {
+            godot_warn!(
+                "编号为{}的插件参数没有声明支持调制，调制量可能会被插件忽略",
+                param_id.get()
+            );
+        }
+        self.audio_access
+            .send_scheduled_input_events(Box::new([ScheduledEvent::new(
+                event_time,
+                to_unknown(ParamModEvent::new(
+                    0,
+                    param_id,
+                    pckn,
+                    amount,
+                    Cookie::empty(),
+                )),
+            )]));
     }
 
     /// 输出音频端口访问句柄。
@@ -178,35 +495,324 @@ impl Host {
     }
 
     /// 主循环，需要一直调用。
-    pub fn process(&mut self) {
-        self.message_processor.process();
-        self.audio_processor_thread
-            .take_if(|audio_processor_thread| {
-                if !audio_processor_thread.is_finished() {
-                    self.audio_access.process();
-                    return false;
+    ///
+    /// 返回：本次循环中产生的、需要由Godot端进一步处理的[`主机事件`](HostEvent)。
+    pub fn process(&mut self) -> Vec<HostEvent> {
+        let mut host_events: Vec<_> = self
+            .message_processor
+            .process()
+            .into_iter()
+            .filter_map(|plugin_message| self.process_plugin_message(plugin_message))
+            .collect();
+        if self.audio_access.take_woke_up() {
+            host_events.push(HostEvent::WokeUp);
+        }
+
+        if let Some(AudioDriver::Thread(audio_processor_thread)) =
+            self.audio_driver.take_if(|audio_driver| {
+                if let AudioDriver::Thread(audio_processor_thread) = audio_driver
+                    && audio_processor_thread.is_finished()
+                {
+                    return true;
                 }
-                true
+                self.audio_access.process();
+                false
             })
-            .map(|audio_processor_thread| {
+            && let Some(audio_processor) = Self::join_audio_processor_thread(audio_processor_thread)
+        {
+            self.deactivate(audio_processor);
+        }
+
+        host_events
+    }
+
+    /// 等待音频线程退出，取回插件已经停止处理的音频处理器。
+    ///
+    /// Clap规定插件必须先在音频线程上停止处理，再在主线程上停用，所以停止处理由音频线程在退出前完成。
+    fn join_audio_processor_thread(
+        audio_processor_thread: JoinHandle<AudioProcessor<Self, f32>>,
+    ) -> Option<AudioProcessor<Self, f32>> {
+        audio_processor_thread
+            .join()
+            .inspect_err(|err| {
                 // 在线程中调用[`HostShared`]的`log`方法，
                 // 或者调用godot_print、godot_warn、godot_err等方法，会导致线程终止。
-                godot_error!(
-                    "音频线程好像有一点似了：{:?}",
-                    audio_processor_thread.join().map_err(|err| {
-                        if let Some(err) = err.downcast_ref::<&'static str>() {
-                            err.to_string()
-                        } else if let Some(err) = err.downcast_ref::<String>() {
-                            err.clone()
-                        } else {
-                            "未知错误".to_string()
-                        }
-                    })
+                godot_error!("音频线程好像有一点似了：{}", panic_message(err.as_ref()));
+            })
+            .ok()
+    }
+
+    /// 停止音频处理并取回音频处理器，之后才能在主线程上停用插件。
+    ///
+    /// 自己的音频线程会在退出前停止插件的处理；外部驱动时则由外部的音频线程在下一次处理时停止，
+    /// 共享的音频处理器仍然保留在驱动中，之后可以放回重新激活的音频处理器。
+    fn stop_audio_processor(&mut self) -> Option<AudioProcessor<Self, f32>> {
+        match self.audio_driver.take()? {
+            AudioDriver::Thread(audio_processor_thread) => {
+                self.audio_access.request_stop();
+                Self::join_audio_processor_thread(audio_processor_thread)
+            }
+            AudioDriver::External(shared_audio_processor) => {
+                let audio_processor = shared_audio_processor.stop();
+                self.audio_driver = Some(AudioDriver::External(shared_audio_processor));
+                audio_processor
+            }
+        }
+    }
+
+    /// 重新运行音频处理，外部驱动时放回共享的音频处理器，否则启动新的音频线程。
+    fn start_audio_processor(&mut self, audio_processor: AudioProcessor<Self, f32>) {
+        match &self.audio_driver {
+            Some(AudioDriver::External(shared_audio_processor)) => {
+                shared_audio_processor.restore(audio_processor);
+            }
+            Some(AudioDriver::Thread(_)) | None => {
+                self.audio_driver = Some(AudioDriver::Thread(spawn(move || audio_processor.run())));
+            }
+        }
+    }
+
+    /// 重启插件：停止处理，停用之后重新激活，再重新查询延迟。
+    ///
+    /// Clap规定插件只能在停用时改变延迟，处于激活状态的插件需要通过请求重启来改变延迟。
+    fn restart(&mut self) -> Option<HostEvent> {
+        let Some(audio_processor) = self.stop_audio_processor() else {
+            // 插件没有激活，不需要重启。
+            return None;
+        };
+        match audio_processor.reactivate(self.message_processor.plugin_instance_mut()) {
+            Ok(audio_processor) => self.start_audio_processor(audio_processor),
+            Err(err) => {
+                godot_error!("重启插件失败，插件已被停用：{err}");
+                return None;
+            }
+        }
+        self.update_latency_frames()
+    }
+
+    /// 重新查询插件的延迟。
+    ///
+    /// 返回：延迟发生变化时的[`HostEvent::LatencyChanged`]。
+    fn update_latency_frames(&mut self) -> Option<HostEvent> {
+        let latency_frames = self.query_latency_frames();
+        if latency_frames == self.latency_frames {
+            return None;
+        }
+        self.latency_frames = latency_frames;
+        Some(HostEvent::LatencyChanged(latency_frames))
+    }
+
+    /// 停用插件。
+    fn deactivate(&mut self, audio_processor: AudioProcessor<Self, f32>) {
+        self.message_processor
+            .plugin_instance_mut()
+            .deactivate(audio_processor.into_stopped());
+    }
+
+    /// 由外部驱动音频处理时共享的音频处理器，由自己的音频线程驱动时为空。
+    pub fn shared_audio_processor(&self) -> Option<&SharedAudioProcessor> {
+        match &self.audio_driver {
+            Some(AudioDriver::External(shared_audio_processor)) => Some(shared_audio_processor),
+            Some(AudioDriver::Thread(_)) | None => None,
+        }
+    }
+
+    /// 离线渲染，渲染期间会通过渲染扩展告知插件当前处于离线模式，见`AudioProcessor::render_offline`。
+    ///
+    /// 渲染模式只能在主线程上设置，所以渲染前先停止音频驱动，保证不会有实时的处理在离线模式下进行，
+    /// 再由主线程代替音频线程同步地完成渲染，渲染结束后恢复实时模式并重新运行音频驱动。
+    ///
+    /// 返回：按端口顺序排列的所有输出通道的音频，插件已经停止处理时为空。
+    pub fn render_offline(
+        &mut self,
+        frames_count: usize,
+        input_channels: Box<[Box<[f32]>]>,
+        events: Box<[ScheduledEvent]>,
+    ) -> Option<Box<[Box<[f32]>]>> {
+        let mut audio_processor = self.stop_audio_processor()?;
+        self.set_render_mode(RenderMode::Offline);
+        let output_channels = audio_processor.render_offline(frames_count, &input_channels, events);
+        self.set_render_mode(RenderMode::Realtime);
+        self.start_audio_processor(audio_processor);
+        Some(output_channels)
+    }
+
+    /// 通过渲染扩展设置插件的渲染模式，插件不支持渲染扩展时什么都不做。
+    fn set_render_mode(&mut self, render_mode: RenderMode) {
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        let Some(plugin_render) = plugin_main_thread_handle.get_extension::<PluginRender>() else {
+            return;
+        };
+        if matches!(render_mode, RenderMode::Offline)
+            && plugin_render.has_hard_realtime_requirement(&mut plugin_main_thread_handle)
+        {
+            godot_warn!("当前Clap插件只能实时渲染，离线渲染的结果可能与实时处理不同");
+        }
+        if let Err(err) = plugin_render.set(&mut plugin_main_thread_handle, render_mode) {
+            godot_warn!("设置插件的渲染模式失败：{err:?}");
+        }
+    }
+
+    /// 处理需要主机自行处理的插件消息。
+    fn process_plugin_message(&mut self, plugin_message: PluginMessage) -> Option<HostEvent> {
+        match plugin_message {
+            PluginMessage::Params(params_message) => self.process_params_message(params_message),
+            PluginMessage::RequestRestart => self.restart(),
+            PluginMessage::LatencyChanged => self.update_latency_frames(),
+            PluginMessage::NotePortsRescanned => {
+                self.note_port_dialects = self.query_note_port_dialects();
+                None
+            }
+            plugin_message => {
+                self.host_shared().log(
+                    LogSeverity::HostMisbehaving,
+                    &format!("主机未处理的插件消息：{:?}", plugin_message),
                 );
-            });
+                None
+            }
+        }
+    }
+
+    fn process_params_message(&mut self, params_message: PluginParamsMessage) -> Option<HostEvent> {
+        match params_message {
+            PluginParamsMessage::Rescan(flags) => {
+                let host_shared = self.host_shared();
+                let mut plugin_main_thread_handle =
+                    self.message_processor.plugin_instance_mut().plugin_handle();
+                if flags.intersects(ParamRescanFlags::INFO | ParamRescanFlags::ALL) {
+                    self.param_access
+                        .rescan(&host_shared, &mut plugin_main_thread_handle);
+                } else {
+                    self.param_access
+                        .refresh_values(&mut plugin_main_thread_handle);
+                }
+                Some(HostEvent::ParamsRescanned(flags))
+            }
+            PluginParamsMessage::Clear(param_id, flags) => {
+                self.audio_access.clear_param_events(param_id, flags);
+                None
+            }
+            PluginParamsMessage::RequestFlush => {
+                self.flush_params();
+                None
+            }
+        }
+    }
+
+    /// 处理插件的参数刷新请求。
+    ///
+    /// 插件处于激活状态时只能在音频线程上刷新，所以交给音频线程在空闲时进行；否则直接在主线程上刷新。
+    fn flush_params(&mut self) {
+        let Some(plugin_params) = self.param_access.plugin_params() else {
+            return;
+        };
+
+        let plugin_instance = self.message_processor.plugin_instance_mut();
+        if plugin_instance.is_active() {
+            if self.audio_driver.is_some() {
+                self.audio_access.request_flush();
+            } else {
+                self.host_shared().log(
+                    LogSeverity::HostMisbehaving,
+                    "插件请求了参数刷新，但插件处于激活状态而音频线程已经不在运行，无法进行刷新",
+                );
+            }
+            return;
+        }
+
+        plugin_params.flush(
+            &mut plugin_instance.plugin_handle(),
+            &InputEvents::empty(),
+            &mut OutputEvents::void(),
+        );
+        let mut plugin_main_thread_handle =
+            self.message_processor.plugin_instance_mut().plugin_handle();
+        self.param_access
+            .refresh_values(&mut plugin_main_thread_handle);
+    }
+
+    /// 插件消息转发器。
+    fn host_shared(&self) -> HostShared {
+        self.message_processor
+            .plugin_instance()
+            .access_shared_handler(|host_shared| host_shared.clone())
+    }
+}
+
+impl Drop for Host {
+    /// 停止音频线程并停用插件，之后插件实例和clap文件才会随字段一起销毁。
+    fn drop(&mut self) {
+        if let Some(audio_processor) = self.stop_audio_processor() {
+            self.deactivate(audio_processor);
+        }
+    }
+}
+
+/// 音频处理的驱动方式。
+#[derive(Clone, Copy, Debug)]
+pub enum AudioDriverMode {
+    /// 由主机自己的音频线程按墙上时钟驱动。
+    Thread,
+    /// 由外部驱动，例如Godot的混音线程，见[`AudioProcessor::process_external`]。
+    External,
+}
+
+/// 音频处理的驱动。
+enum AudioDriver {
+    /// 主机自己的音频线程，结束时返回插件已经停止处理的音频处理器。
+    Thread(JoinHandle<AudioProcessor<Host, f32>>),
+    /// 由外部驱动。
+    External(SharedAudioProcessor),
+}
+
+/// 插件的输入音符端口支持的音符方言。
+struct NotePortDialects {
+    supported: NoteDialects,
+    /// 插件偏好的方言。
+    preferred: Option<NoteDialect>,
+}
+impl NotePortDialects {
+    /// 为midi 1.0消息选择音符方言：优先使用插件偏好的Clap或midi方言，否则优先使用Clap音符事件。
+    ///
+    /// 返回：两种方言都不支持时为空。
+    fn midi_dialect(&self) -> Option<NoteDialect> {
+        match self.preferred {
+            Some(NoteDialect::Clap) if self.supported.contains(NoteDialects::CLAP) => {
+                Some(NoteDialect::Clap)
+            }
+            Some(NoteDialect::Midi) if self.supported.contains(NoteDialects::MIDI) => {
+                Some(NoteDialect::Midi)
+            }
+            _ if self.supported.contains(NoteDialects::CLAP) => Some(NoteDialect::Clap),
+            _ if self.supported.contains(NoteDialects::MIDI) => Some(NoteDialect::Midi),
+            _ => None,
+        }
     }
 }
 
+/// 从线程panic的负载中取出错误信息。
+fn panic_message(err: &(dyn Any + Send)) -> String {
+    if let Some(err) = err.downcast_ref::<&'static str>() {
+        err.to_string()
+    } else if let Some(err) = err.downcast_ref::<String>() {
+        err.clone()
+    } else {
+        "未知错误".to_string()
+    }
+}
+
+/// 主机在主循环中产生的、需要由Godot端进一步处理的事件。
+#[derive(Debug)]
+pub enum HostEvent {
+    /// 插件参数被重新扫描过了，附带插件给出的重新扫描标志。
+    ParamsRescanned(ParamRescanFlags),
+    /// 插件的延迟发生了变化，附带新的延迟帧数。
+    LatencyChanged(u32),
+    /// 插件从睡眠中醒来，重新开始处理。
+    WokeUp,
+}
+
 // 主机信息，惰性初始化
 static HOST_INFO: LazyLock<Option<HostInfo>> = LazyLock::new(|| {
     HostInfo::new(
@@ -270,6 +876,10 @@ impl<H: HostHandlers> From<ProcessingStartError<H>> for HostBuildError {
 /// 读取Clap文件时会出的错
 #[derive(Debug)]
 pub enum ClapFileError {
+    InvalidPath {
+        path: PathBuf,
+        io_error: io::Error,
+    },
     PluginBundleError {
         path: PathBuf,
         plugin_bundle_error: PluginBundleError,
@@ -277,21 +887,29 @@ pub enum ClapFileError {
     NoPluginFactory {
         path: PathBuf,
     },
+    PluginIdNotFound {
+        path: PathBuf,
+        plugin_id: String,
+    },
 }
 impl Error for ClapFileError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
+            ClapFileError::InvalidPath { io_error, .. } => Some(io_error),
             ClapFileError::PluginBundleError {
                 plugin_bundle_error,
                 ..
             } => Some(plugin_bundle_error),
-            ClapFileError::NoPluginFactory { .. } => None,
+            ClapFileError::NoPluginFactory { .. } | ClapFileError::PluginIdNotFound { .. } => None,
         }
     }
 }
 impl Display for ClapFileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            ClapFileError::InvalidPath { path, io_error } => {
+                write!(f, "Clap文件路径无效，{io_error}，来自：{}", path.display())
+            }
             ClapFileError::PluginBundleError {
                 path,
                 plugin_bundle_error,
@@ -304,6 +922,11 @@ impl Display for ClapFileError {
             ClapFileError::NoPluginFactory { path } => {
                 write!(f, "Clap文件不包含插件工厂，来自：{}", path.display())
             }
+            ClapFileError::PluginIdNotFound { path, plugin_id } => write!(
+                f,
+                "Clap文件中找不到编号为{plugin_id}的插件，来自：{}",
+                path.display()
+            ),
         }
     }
 }
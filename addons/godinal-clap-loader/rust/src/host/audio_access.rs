@@ -2,18 +2,43 @@ use crate::{
     clap_input_audio_port_access::ClapInputAudioPortAccess,
     clap_output_audio_port_access::ClapOutputAudioPortAccess,
     clap_transport_event_access::ClapTransportEventAccess,
+    host::audio_processor::{
+        event_buffer::{EventBytes, InputEvent, ScheduledEvent},
+        scheduler::Scheduler,
+    },
+    ring_buffer::{Consumer, Producer},
+};
+use clack_extensions::params::ParamClearFlags;
+use clack_host::{
+    events::{UnknownEvent, event_types::TransportEvent, spaces::CoreEventSpace},
+    utils::ClapId,
 };
-use clack_host::events::{UnknownEvent, event_types::TransportEvent};
 use godot::{prelude::*, register::ConnectHandle};
 use std::{
+    cell::RefCell,
     mem::swap,
     sync::{
         Arc, RwLock,
-        mpsc::{Receiver, Sender},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
     time::{Duration, SystemTime},
 };
 
+/// 插件输出的事件。
+pub struct OutputEvent {
+    /// 事件发生时插件的稳定时间。
+    pub steady_time: u64,
+    event: EventBytes,
+    /// 系统独占事件的数据，在音频线程上复制而来，其他事件为空。
+    pub sys_ex: Option<Box<[u8]>>,
+}
+impl OutputEvent {
+    /// 事件本身。系统独占事件所记录的数据地址在插件处理结束后就已失效，不能再访问，数据见[`Self::sys_ex`]。
+    pub fn event(&self) -> &UnknownEvent {
+        self.event.event()
+    }
+}
+
 /// 插件音频线程的访问句柄。
 pub struct AudioAccess {
     process_time: SystemTime,
@@ -21,28 +46,51 @@ pub struct AudioAccess {
     input_audio_port_accesses: Box<[Gd<ClapInputAudioPortAccess>]>,
     output_audio_port_accesses: Box<[Gd<ClapOutputAudioPortAccess>]>,
 
-    input_event_buffer_tx: Sender<Box<[Box<UnknownEvent>]>>,
-    output_event_buffer_rx: Receiver<Box<[Box<UnknownEvent>]>>,
+    input_event_buffer_tx: RefCell<Producer<InputEvent>>,
+    /// 接收音频线程交还的、插件已经处理完的事件所引用的数据，在主线程上释放。
+    released_payloads_rx: RefCell<Consumer<Box<[u8]>>>,
+    output_event_buffer_rx: RefCell<Consumer<u8>>,
 
     transport_event: Arc<RwLock<Option<TransportEvent>>>,
     clap_transport_event_access: Option<(Gd<ClapTransportEventAccess>, ConnectHandle)>,
+
+    steady_time: Arc<AtomicU64>,
+
+    cleared_params_tx: RefCell<Producer<(ClapId, ParamClearFlags)>>,
+    flush_requested: Arc<AtomicBool>,
+
+    scheduler: Arc<Scheduler>,
+
+    /// Godot端已经得知的插件被唤醒的次数。
+    known_wake_ups_count: u64,
 }
 impl AudioAccess {
     pub fn new(
         input_audio_port_accesses: Box<[Gd<ClapInputAudioPortAccess>]>,
         output_audio_port_accesses: Box<[Gd<ClapOutputAudioPortAccess>]>,
-        input_event_buffer_tx: Sender<Box<[Box<UnknownEvent>]>>,
-        output_event_buffer_rx: Receiver<Box<[Box<UnknownEvent>]>>,
+        input_event_buffer_tx: Producer<InputEvent>,
+        released_payloads_rx: Consumer<Box<[u8]>>,
+        output_event_buffer_rx: Consumer<u8>,
         transport_event: Arc<RwLock<Option<TransportEvent>>>,
+        steady_time: Arc<AtomicU64>,
+        cleared_params_tx: Producer<(ClapId, ParamClearFlags)>,
+        flush_requested: Arc<AtomicBool>,
+        scheduler: Arc<Scheduler>,
     ) -> Self {
         Self {
             process_time: SystemTime::now(),
             input_audio_port_accesses,
             output_audio_port_accesses,
-            input_event_buffer_tx,
-            output_event_buffer_rx,
+            input_event_buffer_tx: RefCell::new(input_event_buffer_tx),
+            released_payloads_rx: RefCell::new(released_payloads_rx),
+            output_event_buffer_rx: RefCell::new(output_event_buffer_rx),
             transport_event,
             clap_transport_event_access: None,
+            steady_time,
+            cleared_params_tx: RefCell::new(cleared_params_tx),
+            flush_requested,
+            scheduler,
+            known_wake_ups_count: 0,
         }
     }
 
@@ -53,15 +101,114 @@ impl AudioAccess {
         &self.output_audio_port_accesses
     }
 
+    /// 发送事件，事件头中的时间会被视为相对于音频线程下一次处理开始时的帧偏移。
     pub fn send_input_event_buffers(&self, input_event_buffers: Box<[Box<UnknownEvent>]>) {
-        self.input_event_buffer_tx
-            .send(input_event_buffers)
-            .unwrap_or_else(|err| {
-                godot_error!("ClapPluginInstance对应的事件输入缓冲已不复存在：{:?}", err)
+        self.send_scheduled_input_events(
+            input_event_buffers
+                .into_iter()
+                .map(ScheduledEvent::from)
+                .collect(),
+        );
+    }
+    /// 发送带有调度时间的事件，并唤醒音频线程让事件尽快送达插件。
+    pub fn send_scheduled_input_events(&self, scheduled_events: Box<[ScheduledEvent]>) {
+        let mut input_event_buffer_tx = self.input_event_buffer_tx.borrow_mut();
+        let dropped_events_count = scheduled_events
+            .into_iter()
+            .filter_map(|scheduled_event| {
+                let Some(input_event) = InputEvent::new(scheduled_event) else {
+                    godot_error!("事件过长，无法送往音频线程");
+                    return None;
+                };
+                input_event_buffer_tx.push(input_event).err()
+            })
+            .count();
+        if dropped_events_count > 0 {
+            godot_error!("音频线程的事件输入缓冲已满，丢弃了{dropped_events_count}个事件");
+        }
+        self.scheduler.wake();
+    }
+
+    /// 取出插件输出的所有事件。
+    pub fn pop_output_events(&self) -> Vec<OutputEvent> {
+        let mut output_event_buffer_rx = self.output_event_buffer_rx.borrow_mut();
+        let mut events = Vec::new();
+        let mut event_length = [0; size_of::<u32>()];
+        let mut event_steady_time = [0; size_of::<u64>()];
+        let mut payload_length = [0; size_of::<u32>()];
+        // 每个事件都是连同长度、稳定时间和所引用的数据一起整体写入的，读到了长度就一定能读到整个事件。
+        while output_event_buffer_rx.pop_slice(&mut event_length) == event_length.len() {
+            let mut event = vec![0; u32::from_ne_bytes(event_length) as usize].into_boxed_slice();
+            let is_complete = pop_exact(&mut output_event_buffer_rx, &mut event_steady_time)
+                && pop_exact(&mut output_event_buffer_rx, &mut event)
+                && pop_exact(&mut output_event_buffer_rx, &mut payload_length);
+            let mut payload =
+                vec![0; u32::from_ne_bytes(payload_length) as usize].into_boxed_slice();
+            if !is_complete || !pop_exact(&mut output_event_buffer_rx, &mut payload) {
+                // 字节流已经错位，之后读到的长度都不可信，只能全部丢弃。
+                godot_error!("插件输出的事件不完整，丢弃了所有尚未读取的事件");
+                output_event_buffer_rx.clear();
+                break;
+            }
+
+            let Some(event) = EventBytes::from_bytes(&event) else {
+                godot_warn!("插件输出的事件过长或格式有误，已丢弃");
+                continue;
+            };
+            let sys_ex = matches!(
+                event.event().as_core_event(),
+                Some(CoreEventSpace::MidiSysEx(_))
+            )
+            .then_some(payload);
+            events.push(OutputEvent {
+                steady_time: u64::from_ne_bytes(event_steady_time),
+                event,
+                sys_ex,
             });
+        }
+        events
+    }
+
+    /// 按清除标志丢弃该参数尚未生效的参数值事件或调制事件。
+    pub fn clear_param_events(&self, param_id: ClapId, flags: ParamClearFlags) {
+        if self
+            .cleared_params_tx
+            .borrow_mut()
+            .push((param_id, flags))
+            .is_err()
+        {
+            godot_error!("丢弃参数事件失败，音频线程的事件输入缓冲已满");
+        }
+        self.scheduler.wake();
+    }
+
+    /// 请求音频线程在空闲时进行一次参数刷新。
+    pub fn request_flush(&self) {
+        self.flush_requested.store(true, Ordering::Release);
+        self.scheduler.wake();
+    }
+
+    /// 请求音频线程停止处理并退出。
+    pub fn request_stop(&self) {
+        self.scheduler.request_stop();
+    }
+
+    /// 插件的稳定时间，即音频线程已经处理完的总帧数。
+    pub fn steady_time(&self) -> u64 {
+        self.steady_time.load(Ordering::Acquire)
+    }
+
+    /// 插件是否正在睡眠，见`AudioProcessor::should_sleep`。
+    pub fn is_sleeping(&self) -> bool {
+        self.scheduler.is_sleeping()
     }
-    pub fn output_event_buffer_rx(&self) -> &Receiver<Box<[Box<UnknownEvent>]>> {
-        &self.output_event_buffer_rx
+
+    /// 插件自上次调用以来是否从睡眠中醒来过。
+    pub fn take_woke_up(&mut self) -> bool {
+        let wake_ups_count = self.scheduler.wake_ups_count();
+        let woke_up = wake_ups_count != self.known_wake_ups_count;
+        self.known_wake_ups_count = wake_ups_count;
+        woke_up
     }
 
     pub fn get_clap_transport_event_access(&self) -> Option<&Gd<ClapTransportEventAccess>> {
@@ -119,7 +266,18 @@ impl AudioAccess {
             });
         self.process_input_audio(time.as_secs_f64());
         self.process_output_audio();
+        self.released_payloads_rx
+            .borrow_mut()
+            .pop_iter()
+            .for_each(drop);
     }
 
     // todo: 添加cv控制。
 }
+
+/// 从环形缓冲中读满`values`。
+///
+/// 返回：是否读满。
+fn pop_exact(consumer: &mut Consumer<u8>, values: &mut [u8]) -> bool {
+    consumer.pop_slice(values) == values.len()
+}
@@ -1,40 +1,79 @@
 mod audio_buffer;
-mod event_buffer;
-
-use crate::host::{
-    Host, HostBuildError,
-    audio_access::AudioAccess,
-    audio_processor::{
-        audio_buffer::{
-            AudioBuffer,
-            channel::{InputChannel, OutputChannel},
+pub mod event_buffer;
+pub mod scheduler;
+
+use crate::{
+    host::{
+        Host, HostBuildError,
+        audio_access::AudioAccess,
+        audio_processor::{
+            audio_buffer::{
+                AudioBuffer,
+                channel::{InputChannel, OutputChannel},
+                silence,
+            },
+            event_buffer::{EventBuffer, InputHandle, OutputHandle, ScheduledEvent},
+            scheduler::Scheduler,
         },
-        event_buffer::{EventBuffer, InputHandle, OutputHandle},
+        host_handlers_impl::host_audio_processor::HostAudioProcessor,
     },
-    host_handlers_impl::host_audio_processor::HostAudioProcessor,
+    ring_buffer::ring_buffer,
+};
+use clack_extensions::{
+    audio_ports::PluginAudioPorts,
+    params::PluginParams,
+    tail::{PluginTail, TailLength},
 };
-use clack_extensions::audio_ports::PluginAudioPorts;
 use clack_host::{
     events::event_types::TransportEvent,
     host::HostHandlers,
     plugin::PluginInstance,
-    process::{PluginAudioConfiguration, StartedPluginAudioProcessor},
+    process::{
+        PluginAudioConfiguration, PluginAudioProcessor, ProcessStatus, StoppedPluginAudioProcessor,
+    },
 };
 use godot::{
-    classes::{AudioStreamGenerator, audio_stream_generator::AudioStreamGeneratorMixRate},
+    classes::{
+        AudioStreamGenerator, audio_stream_generator::AudioStreamGeneratorMixRate,
+        native::AudioFrame,
+    },
+    global::godot_warn,
     obj::NewGd,
 };
 use itertools::Itertools;
 use std::{
     cell::OnceCell,
     iter::repeat_n,
-    sync::{Arc, RwLock, mpsc::channel},
-    time::SystemTime,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
+/// 每个最大延迟时间内音频线程进行处理的次数。
+///
+/// 音频线程每次处理完都会睡眠到下一次处理的截止时间，所以这个值越大，输出的延迟越稳定，但唤醒也越频繁。
+const PROCESSES_PER_MAX_LATENCY: usize = 4;
+
+/// Godot端每帧才会取走一次音频线程输出的数据，发往Godot的环形缓冲至少要能容纳这段时长的数据，以免主线程稍有卡顿就丢失数据。
+const MIN_OUTPUT_RING_BUFFER_SECONDS: f64 = 0.5;
+
+/// 事件相关的缓冲至少预先分配的事件个数。
+const MIN_EVENTS_CAPACITY: usize = 1024;
+
+/// 发往Godot的事件缓冲按每个事件的平均字节数预先分配空间。
+const AVERAGE_EVENT_LENGTH: usize = 64;
+
+/// 离线渲染时每次处理的帧数，固定的块大小保证了相同的输入总是得到相同的输出。
+const OFFLINE_RENDER_BLOCK_FRAMES_COUNT: usize = 512;
+
+/// 主线程等待外部的音频线程停止插件处理的最长时间，足以覆盖Godot混音线程的若干次回调。
+const EXTERNAL_STOP_TIMEOUT: Duration = Duration::from_millis(200);
+
 pub struct AudioProcessor<T: HostHandlers, F> {
-    /// 插件音频处理器。
-    plugin_audio_processor: StartedPluginAudioProcessor<T>,
+    /// 插件音频处理器，只在音频线程上开始处理，插件睡眠时停止处理。
+    plugin_audio_processor: PluginAudioProcessor<T>,
     /// 音频设置。
     plugin_audio_configuration: PluginAudioConfiguration,
 
@@ -52,11 +91,28 @@ pub struct AudioProcessor<T: HostHandlers, F> {
     /// 用于同步插件处理与宿主播放时间轴。
     transport_event: Arc<RwLock<Option<TransportEvent>>>,
 
-    /// 本插件实例生成的时间。
-    start_time: SystemTime,
+    /// 开始按墙上时钟计时的时间，离线渲染之后会被重置。
+    start_time: Instant,
+    /// 开始按墙上时钟计时的时候的稳定时间。
+    start_steady_time: u64,
+    /// 两次处理之间间隔的帧数。
+    scheduling_frames_count: u64,
+    /// 音频线程的调度器，同时记录插件的睡眠状态。
+    scheduler: Arc<Scheduler>,
 
     /// 已处理帧数。
     steady_time: u64,
+    /// 与Godot端共享的已处理帧数，用于让Godot端按插件的稳定时间调度事件。
+    shared_steady_time: Arc<AtomicU64>,
+
+    /// 插件侧的参数功能句柄，插件不支持参数功能时为空。
+    plugin_params: Option<PluginParams>,
+    /// 插件是否请求了参数刷新。
+    flush_requested: Arc<AtomicBool>,
+
+    plugin_tail: Option<PluginTail>,
+    /// 插件返回[`ProcessStatus::Tail`]时，输入静音之后还需要继续处理的帧数。
+    tail_frames_count_left: u64,
 }
 impl AudioProcessor<Host, f32> {
     pub fn try_new(
@@ -75,14 +131,13 @@ impl AudioProcessor<Host, f32> {
             max_frames_count: buffer_frames_count as u32,
         };
 
-        let plugin_audio_processor = plugin_instance
-            .activate(
-                |host_shared, host_main_thread| {
-                    HostAudioProcessor::new(host_shared, host_main_thread.clone())
-                },
-                plugin_audio_configuration,
-            )?
-            .start_processing()?;
+        // 开始处理需要在音频线程上进行，留到第一次处理时。
+        let plugin_audio_processor = PluginAudioProcessor::Stopped(plugin_instance.activate(
+            |host_shared, host_main_thread| {
+                HostAudioProcessor::new(host_shared, host_main_thread.clone())
+            },
+            plugin_audio_configuration,
+        )?);
 
         let host_shared = plugin_instance.access_shared_handler(|host_shared| host_shared.clone());
         let mut plugin_main_thread_handle = plugin_instance.plugin_handle();
@@ -102,22 +157,42 @@ impl AudioProcessor<Host, f32> {
             buffer_frames_count,
             sample_rate,
         );
+        let output_ring_buffer_frames_count =
+            buffer_frames_count.max((sample_rate * MIN_OUTPUT_RING_BUFFER_SECONDS) as usize);
         let (output_audio_buffer, output_audio_port_accesses) =
             AudioBuffer::<OutputChannel<_>>::new(
                 &host_shared,
                 &mut plugin_main_thread_handle,
                 &plugin_audio_ports,
+                buffer_frames_count,
+                output_ring_buffer_frames_count,
                 audio_stream_generator,
             );
 
-        let (input_event_buffer_tx, input_event_buffer_rx): (_, InputHandle) = channel();
-        let input_event_buffer = EventBuffer::<InputHandle>::new(input_event_buffer_rx);
-        let (output_event_buffer_tx, output_event_buffer_rx): (OutputHandle, _) = channel();
-        let output_event_buffer = EventBuffer::<OutputHandle>::new(output_event_buffer_tx);
+        let events_capacity = buffer_frames_count.max(MIN_EVENTS_CAPACITY);
+        let (input_event_buffer_tx, input_event_buffer_rx) = ring_buffer(events_capacity);
+        let (cleared_params_tx, cleared_params_rx) = ring_buffer(events_capacity);
+        let (released_payloads_tx, released_payloads_rx) = ring_buffer(events_capacity);
+        let input_event_buffer = EventBuffer::<InputHandle>::new(
+            InputHandle::new(
+                input_event_buffer_rx,
+                cleared_params_rx,
+                released_payloads_tx,
+                events_capacity,
+            ),
+            events_capacity,
+        );
+        let (output_event_buffer_tx, output_event_buffer_rx): (OutputHandle, _) =
+            ring_buffer(events_capacity * AVERAGE_EVENT_LENGTH);
+        let output_event_buffer =
+            EventBuffer::<OutputHandle>::new(output_event_buffer_tx, events_capacity);
 
-        let start_time = SystemTime::now();
+        let start_time = Instant::now();
+        let scheduler = host_shared.scheduler().clone();
 
         let transport_event = Arc::new(RwLock::new(None));
+        let shared_steady_time = Arc::new(AtomicU64::new(0));
+        let flush_requested = Arc::new(AtomicBool::new(false));
 
         Ok((
             Self {
@@ -129,73 +204,260 @@ impl AudioProcessor<Host, f32> {
                 output_event_buffer,
                 transport_event: transport_event.clone(),
                 start_time,
+                start_steady_time: 0,
+                scheduling_frames_count: (buffer_frames_count / PROCESSES_PER_MAX_LATENCY).max(1)
+                    as u64,
+                scheduler: scheduler.clone(),
                 steady_time: 0,
+                shared_steady_time: shared_steady_time.clone(),
+                plugin_params: plugin_main_thread_handle.get_extension::<PluginParams>(),
+                flush_requested: flush_requested.clone(),
+                plugin_tail: plugin_main_thread_handle.get_extension::<PluginTail>(),
+                tail_frames_count_left: 0,
             },
             AudioAccess::new(
                 input_audio_port_accesses,
                 output_audio_port_accesses,
                 input_event_buffer_tx,
+                released_payloads_rx,
                 output_event_buffer_rx,
                 transport_event,
+                shared_steady_time,
+                cleared_params_tx,
+                flush_requested,
+                scheduler,
             ),
         ))
     }
 
     fn process_batches_before(&mut self) {
         self.input_audio_buffer.process();
+        self.input_event_buffer.process(self.steady_time);
     }
 
-    fn process_batch(&mut self, buffer_frames_count: usize, transport: Option<&TransportEvent>) {
+    fn process_batch(&mut self, buffer_frames_count: usize) {
+        let is_input_quiet = self.input_audio_buffer.is_quiet(buffer_frames_count)
+            && !self
+                .input_event_buffer
+                .has_due_events(self.steady_time, buffer_frames_count)
+            && !self.flush_requested.load(Ordering::Acquire);
+        if self.scheduler.is_sleeping() {
+            if is_input_quiet {
+                self.skip_batch(buffer_frames_count);
+                return;
+            }
+            self.scheduler.wake_up();
+        }
+        if !self.plugin_audio_processor.is_started() {
+            // 插件刚开始处理或者刚从睡眠中醒来，拖尾需要重新计算。
+            self.tail_frames_count_left = 0;
+        }
+        let plugin_audio_processor = match self.plugin_audio_processor.ensure_processing_started() {
+            Ok(plugin_audio_processor) => plugin_audio_processor,
+            Err(plugin_instance_error) => {
+                eprintln!("插件开始处理失败：{plugin_instance_error}");
+                self.skip_batch(buffer_frames_count);
+                return;
+            }
+        };
+
         let input_audio_buffer = self.input_audio_buffer.pop_buffer(buffer_frames_count);
         let mut output_audio_buffer = self.output_audio_buffer.pop_buffer(buffer_frames_count);
 
-        let input_events = self.input_event_buffer.pop_buffer();
+        let input_events = self
+            .input_event_buffer
+            .pop_buffer(self.steady_time, buffer_frames_count);
         let mut output_events = self.output_event_buffer.pop_buffer();
 
-        match self.plugin_audio_processor.process(
+        // 音频处理本身就会完成参数刷新。
+        self.flush_requested.store(false, Ordering::Release);
+        let process_result = plugin_audio_processor.process(
             &input_audio_buffer,
             &mut output_audio_buffer,
             &input_events,
             &mut output_events,
             Some(self.steady_time),
-            transport,
-        ) {
+            read_transport_event(&self.transport_event).as_ref(),
+        );
+        match process_result {
             Ok(process_status) => {
-                // todo: 对process_status进行处理，优化本process方法的执行时机。
+                if self.should_sleep(process_status, is_input_quiet, buffer_frames_count) {
+                    // 插件睡眠前需要停止处理，醒来时再重新开始处理。
+                    self.plugin_audio_processor.ensure_processing_stopped();
+                    self.scheduler.sleep();
+                }
             }
             Err(plugin_instance_error) => {
                 eprintln!("音频处理运行时错误：{plugin_instance_error}");
             }
         }
+        // 输出事件的时间只在本次处理之内有意义，需要趁现在换算为稳定时间。
+        self.output_event_buffer.process(self.steady_time);
 
+        self.advance_steady_time(buffer_frames_count);
+    }
+
+    /// 插件睡眠期间代替插件处理：丢弃静音的输入，输出静音。
+    fn skip_batch(&mut self, buffer_frames_count: usize) {
+        self.input_audio_buffer.pop_buffer(buffer_frames_count);
+        self.output_audio_buffer.pop_buffer(buffer_frames_count);
+        self.advance_steady_time(buffer_frames_count);
+    }
+
+    fn advance_steady_time(&mut self, buffer_frames_count: usize) {
         self.steady_time += buffer_frames_count as u64;
+        self.shared_steady_time
+            .store(self.steady_time, Ordering::Release);
+    }
+
+    /// 根据插件处理后返回的状态，判断插件接下来能否睡眠。
+    /// - `is_input_quiet`: 本次处理的输入音频是否为静音，且没有输入事件。
+    fn should_sleep(
+        &mut self,
+        process_status: ProcessStatus,
+        is_input_quiet: bool,
+        buffer_frames_count: usize,
+    ) -> bool {
+        match process_status {
+            ProcessStatus::Continue => false,
+            ProcessStatus::ContinueIfNotQuiet => {
+                is_input_quiet && self.output_audio_buffer.is_quiet(buffer_frames_count)
+            }
+            ProcessStatus::Tail => {
+                if !is_input_quiet {
+                    // 拖尾从输入变为静音时开始计算，每次有输入都重新查询，插件可能随时改变拖尾长度。
+                    self.tail_frames_count_left = self.tail_frames_count();
+                    return false;
+                }
+                self.tail_frames_count_left = self
+                    .tail_frames_count_left
+                    .saturating_sub(buffer_frames_count as u64);
+                self.tail_frames_count_left == 0
+            }
+            ProcessStatus::Sleep => true,
+        }
+    }
+
+    /// 向插件查询拖尾的帧数，插件不支持拖尾扩展时视为没有拖尾，无限长的拖尾为[`u64::MAX`]。
+    fn tail_frames_count(&mut self) -> u64 {
+        let Some(plugin_tail) = self.plugin_tail else {
+            return 0;
+        };
+        let PluginAudioProcessor::Started(plugin_audio_processor) =
+            &mut self.plugin_audio_processor
+        else {
+            return 0;
+        };
+        match plugin_tail.get(&mut plugin_audio_processor.plugin_handle()) {
+            TailLength::Finite(frames_count) => frames_count as u64,
+            TailLength::Infinite => u64::MAX,
+        }
+    }
+
+    /// 插件请求了参数刷新，而当前又没有需要处理的帧时，单独进行一次参数刷新。
+    ///
+    /// 插件处于激活状态时，参数刷新只能在音频线程上进行。
+    fn flush_params(&mut self) {
+        if !self.flush_requested.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let Some(plugin_params) = self.plugin_params else {
+            return;
+        };
+        let PluginAudioProcessor::Started(plugin_audio_processor) =
+            &mut self.plugin_audio_processor
+        else {
+            // 插件停止了处理，唤醒插件，由接下来的音频处理完成参数刷新。
+            self.scheduler.wake_up();
+            return;
+        };
+
+        self.input_event_buffer.process(self.steady_time);
+        // 只送出已经到期的事件，包括恰好在当前稳定时间生效的，其余的事件留给之后的音频处理。
+        let input_events = self.input_event_buffer.pop_buffer(self.steady_time, 1);
+        let mut output_events = self.output_event_buffer.pop_buffer();
+        plugin_params.flush_active(
+            &mut plugin_audio_processor.plugin_handle(),
+            &input_events,
+            &mut output_events,
+        );
+        self.output_event_buffer.process(self.steady_time);
     }
 
     fn process_batches_after(&mut self) {
         self.output_audio_buffer.process();
-        self.output_event_buffer.process();
     }
 
-    pub fn process(&mut self) {
-        let transport_event = self.transport_event.clone();
-        let transport_event = transport_event.read().unwrap_or_else(|err| {
-            panic!(
-                "获取transport_event的读权限失败，大概是宿主在更改这个属性的时候出了什么意外：{err}"
+    /// 插件是否处于开始处理的状态。
+    fn is_started(&self) -> bool {
+        self.plugin_audio_processor.is_started()
+    }
+
+    /// 停止插件的处理，需要在音频线程上调用。
+    pub fn stop_processing(&mut self) {
+        self.plugin_audio_processor.ensure_processing_stopped();
+    }
+
+    /// 取出已停止的插件音频处理器，交给主线程停用插件。
+    ///
+    /// 插件应当已经在音频线程上停止了处理，否则只能在调用的线程上停止处理。
+    pub fn into_stopped(self) -> StoppedPluginAudioProcessor<Host> {
+        self.plugin_audio_processor.into_stopped()
+    }
+
+    /// 停用插件之后重新激活，保留原有的音频设置、缓冲区和Godot端的访问句柄，需要在主线程上调用。
+    ///
+    /// 插件需要已经在音频线程上停止了处理，重新激活后的插件会在下一次处理时开始处理。
+    pub fn reactivate(
+        self,
+        plugin_instance: &mut PluginInstance<Host>,
+    ) -> Result<Self, HostBuildError> {
+        plugin_instance.deactivate(self.plugin_audio_processor.into_stopped());
+        let plugin_audio_processor = PluginAudioProcessor::Stopped(plugin_instance.activate(
+            |host_shared, host_main_thread| {
+                HostAudioProcessor::new(host_shared, host_main_thread.clone())
+            },
+            self.plugin_audio_configuration,
+        )?);
+        Ok(Self {
+            plugin_audio_processor,
+            tail_frames_count_left: 0,
+            ..self
+        })
+    }
+
+    /// 音频线程的主循环，直到Godot端通过[`Scheduler::request_stop`]请求停止为止。
+    ///
+    /// 返回：插件已经停止处理的音频处理器，需要交给主线程停用插件。
+    pub fn run(mut self) -> Self {
+        // 音频线程可能是在插件重启或离线渲染之后重新运行的，需要重新开始计时，否则会一下子补上停止期间的所有帧。
+        self.start_time = Instant::now();
+        self.start_steady_time = self.steady_time;
+        let scheduler = self.scheduler.clone();
+        while scheduler.wait_until(self.process()) {}
+        self.stop_processing();
+        self
+    }
+
+    /// 下一次处理的截止时间，即积攒够[`Self::scheduling_frames_count`]帧的时间。
+    fn next_deadline(&self) -> Instant {
+        self.start_time
+            + Duration::from_secs_f64(
+                (self.steady_time - self.start_steady_time + self.scheduling_frames_count) as f64
+                    / self.plugin_audio_configuration.sample_rate,
             )
-        });
-        let transport_event = transport_event.as_ref();
+    }
 
-        let time = match SystemTime::now().duration_since(self.start_time) {
-            Ok(time_delta) => time_delta,
-            Err(system_time_error) => {
-                eprintln!("插件已运行时间计算错误：{system_time_error}");
-                return;
-            }
-        };
+    /// 处理所有已经到期的帧。
+    ///
+    /// 返回：下一次处理的截止时间。
+    fn process(&mut self) -> Instant {
+        let time = self.start_time.elapsed();
         let frames_count_delta = (time.as_secs_f64() * self.plugin_audio_configuration.sample_rate)
-            - self.steady_time as f64;
+            - (self.steady_time - self.start_steady_time) as f64;
         if frames_count_delta < 1.0 {
-            return;
+            self.flush_params();
+            return self.next_deadline();
         }
 
         let frames_count_delta = frames_count_delta as usize;
@@ -208,11 +470,183 @@ impl AudioProcessor<Host, f32> {
         self.process_batches_before();
         let is_processed = OnceCell::<()>::new();
         for frames_count in frames_counts {
-            self.process_batch(frames_count, transport_event);
+            self.process_batch(frames_count);
             is_processed.get_or_init(|| ());
         }
         if is_processed.get().is_some() {
             self.process_batches_after();
         }
+        self.next_deadline()
+    }
+
+    /// 由外部驱动的处理，例如Godot的混音线程，处理的帧数完全由外部决定，不依赖墙上时钟。
+    ///
+    /// `input`会被叠加到插件的主输入端口上，插件主输出端口的输出会被写入`output`，两者的长度应当相同。
+    /// 其他端口照常与对应的端口访问句柄交换数据。
+    pub fn process_external(&mut self, input: &[AudioFrame], output: &mut [AudioFrame]) {
+        if output.is_empty() {
+            return;
+        }
+
+        self.process_batches_before();
+        self.input_audio_buffer.mix_stereo(input);
+        let max_frames_count = self.plugin_audio_configuration.max_frames_count as usize;
+        let mut frames_count_left = output.len();
+        while frames_count_left > 0 {
+            let frames_count = frames_count_left.min(max_frames_count);
+            self.process_batch(frames_count);
+            frames_count_left -= frames_count;
+        }
+        self.output_audio_buffer.copy_stereo(output);
+        self.process_batches_after();
+    }
+
+    /// 离线渲染：不依赖墙上时钟，以固定的块大小同步处理`frames_count`帧。
+    ///
+    /// 需要在音频驱动停止之后调用，调用的线程在渲染期间代替音频线程。
+    /// 渲染前后都会重置插件，并丢弃所有尚未处理的音频和事件。
+    /// 渲染使用独立的、从0开始的稳定时间，保证相同的输入总是得到相同的输出，渲染结束后再回到原来的时间轴。
+    /// 渲染的音频不会发往Godot端的端口访问句柄。
+    /// - `input_channels`: 按端口顺序排列的所有输入通道的音频，缺少的部分视为静音。
+    /// - `events`: 以相对于渲染开始时的帧偏移调度的事件。
+    ///
+    /// 返回：按端口顺序排列的所有输出通道的音频。
+    pub fn render_offline(
+        &mut self,
+        frames_count: usize,
+        input_channels: &[Box<[f32]>],
+        events: Box<[ScheduledEvent]>,
+    ) -> Box<[Box<[f32]>]> {
+        let steady_time = self.steady_time;
+        self.reset();
+        self.steady_time = 0;
+        self.input_event_buffer.schedule(self.steady_time, events);
+        self.input_audio_buffer.mix_channels(input_channels);
+
+        let block_frames_count = OFFLINE_RENDER_BLOCK_FRAMES_COUNT
+            .min(self.plugin_audio_configuration.max_frames_count as usize);
+        let mut frames_count_left = frames_count;
+        while frames_count_left > 0 {
+            let frames_count = frames_count_left.min(block_frames_count);
+            self.process_batch(frames_count);
+            frames_count_left -= frames_count;
+        }
+        let output_channels = self.output_audio_buffer.take_channels();
+
+        self.reset();
+        self.steady_time = steady_time;
+        self.shared_steady_time
+            .store(self.steady_time, Ordering::Release);
+        self.stop_processing();
+        output_channels
+    }
+
+    /// 重置插件，并丢弃所有尚未处理的音频和事件。
+    ///
+    /// 重置之后插件的状态已经回到了初始状态，插件即使在睡眠也需要醒来照常处理。
+    fn reset(&mut self) {
+        self.scheduler.wake_up();
+        self.tail_frames_count_left = 0;
+        match self.plugin_audio_processor.ensure_processing_started() {
+            Ok(plugin_audio_processor) => plugin_audio_processor.reset(),
+            Err(plugin_instance_error) => {
+                eprintln!("插件开始处理失败：{plugin_instance_error}");
+            }
+        }
+        self.input_audio_buffer.clear();
+        self.output_audio_buffer.take_channels();
+        self.input_event_buffer.clear(self.steady_time);
+    }
+}
+
+/// 读取宿主的播放传输状态。
+fn read_transport_event(
+    transport_event: &RwLock<Option<TransportEvent>>,
+) -> RwLockReadGuard<'_, Option<TransportEvent>> {
+    transport_event.read().unwrap_or_else(|err| {
+        panic!("获取transport_event的读权限失败，大概是宿主在更改这个属性的时候出了什么意外：{err}")
+    })
+}
+
+/// 由外部驱动音频处理时，与外部共享的音频处理器。
+///
+/// 主机销毁时会请求外部的音频线程停止插件的处理，再将音频处理器取走，之后外部的处理只会输出静音。
+#[derive(Clone)]
+pub struct SharedAudioProcessor(Arc<SharedAudioProcessorState>);
+struct SharedAudioProcessorState {
+    audio_processor: Mutex<Option<AudioProcessor<Host, f32>>>,
+    /// 主线程请求外部的音频线程停止插件的处理。
+    stop_requested: AtomicBool,
+    /// 外部的音频线程停止插件的处理之后通知主线程。
+    stopped: Condvar,
+}
+impl SharedAudioProcessor {
+    pub fn new(audio_processor: AudioProcessor<Host, f32>) -> Self {
+        Self(Arc::new(SharedAudioProcessorState {
+            audio_processor: Mutex::new(Some(audio_processor)),
+            stop_requested: AtomicBool::new(false),
+            stopped: Condvar::new(),
+        }))
+    }
+
+    fn audio_processor(&self) -> MutexGuard<'_, Option<AudioProcessor<Host, f32>>> {
+        self.0
+            .audio_processor
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 请求外部的音频线程停止插件的处理，并取走音频处理器，需要在主线程上调用。
+    ///
+    /// Clap规定停止处理只能在音频线程上进行，所以由外部的音频线程在下一次处理时完成。
+    /// 外部的音频线程可能已经不再处理了，例如音频流已经停止播放，等待超时之后只能在主线程上停止处理。
+    pub fn stop(&self) -> Option<AudioProcessor<Host, f32>> {
+        self.0.stop_requested.store(true, Ordering::Release);
+        let (mut audio_processor, wait_timeout_result) = self
+            .0
+            .stopped
+            .wait_timeout_while(
+                self.audio_processor(),
+                EXTERNAL_STOP_TIMEOUT,
+                |audio_processor| {
+                    audio_processor
+                        .as_ref()
+                        .is_some_and(AudioProcessor::is_started)
+                },
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        if wait_timeout_result.timed_out() {
+            godot_warn!("外部的音频线程没有及时停止插件的处理，只能在主线程上停止");
+        }
+        audio_processor.take()
+    }
+
+    /// 放回重新激活的音频处理器，外部的音频线程之后会照常进行处理。
+    pub fn restore(&self, audio_processor: AudioProcessor<Host, f32>) {
+        let mut shared_audio_processor = self.audio_processor();
+        *shared_audio_processor = Some(audio_processor);
+        self.0.stop_requested.store(false, Ordering::Release);
+    }
+
+    /// 在外部的音频线程上进行处理，见[`AudioProcessor::process_external`]。
+    ///
+    /// 不会阻塞外部的音频线程：主线程正在操作音频处理器而持有锁时，直接输出静音。
+    /// 主线程请求了停止时，在这里停止插件的处理并通知主线程。
+    pub fn process(&self, input: &[AudioFrame], output: &mut [AudioFrame]) {
+        let Ok(mut audio_processor) = self.0.audio_processor.try_lock() else {
+            silence(output);
+            return;
+        };
+        let Some(audio_processor) = audio_processor.as_mut() else {
+            silence(output);
+            return;
+        };
+        if self.0.stop_requested.load(Ordering::Acquire) {
+            audio_processor.stop_processing();
+            self.0.stopped.notify_all();
+            silence(output);
+            return;
+        }
+        audio_processor.process_external(input, output);
     }
 }
@@ -20,7 +20,10 @@ use clack_host::{
     plugin::PluginMainThreadHandle,
     prelude::{AudioPorts, InputAudioBuffers, OutputAudioBuffers},
 };
-use godot::{classes::AudioStreamGenerator, obj::Gd};
+use godot::{
+    classes::{AudioStreamGenerator, native::AudioFrame},
+    obj::Gd,
+};
 use itertools::multiunzip;
 
 pub struct AudioBuffer<Channel> {
@@ -28,12 +31,19 @@ pub struct AudioBuffer<Channel> {
     ports: Box<[Port<Channel>]>,
 }
 
-impl<F: Clone> AudioBuffer<InputChannel<F>> {
+impl<F: Copy> AudioBuffer<InputChannel<F>> {
     pub fn process(&mut self) {
         for audio_port_buffer in &mut self.ports {
             audio_port_buffer.process();
         }
     }
+
+    /// 丢弃所有尚未送往插件的音频数据。
+    pub fn clear(&mut self) {
+        for audio_port_buffer in &mut self.ports {
+            audio_port_buffer.clear();
+        }
+    }
 }
 impl AudioBuffer<InputChannel<f32>> {
     pub fn new(
@@ -90,6 +100,26 @@ impl AudioBuffer<InputChannel<f32>> {
                 .map(|port| port.pop_buffer(frames_count)),
         )
     }
+
+    /// 将各个通道的音频按端口顺序依次叠加到所有输入端口的通道上。
+    pub fn mix_channels(&mut self, channels: &[Box<[f32]>]) {
+        let mut channels = channels.iter().map(|channel| channel.as_ref());
+        for port in &mut self.ports {
+            port.mix_channels(&mut channels);
+        }
+    }
+
+    /// 接下来的`frames_count`帧输入是否全部为静音，尚未收到的部分也视为静音。
+    pub fn is_quiet(&self, frames_count: usize) -> bool {
+        self.ports.iter().all(|port| port.is_quiet(frames_count))
+    }
+
+    /// 将立体声音频叠加到插件的主输入端口（第一个输入端口）上，插件没有输入端口时会被丢弃。
+    pub fn mix_stereo(&mut self, frames: &[AudioFrame]) {
+        if let Some(port) = self.ports.first_mut() {
+            port.mix_stereo(frames);
+        }
+    }
 }
 #[deprecated(note = "在以Cardinal.clap进行开发测试时，发现f64将导致段错误。故不要使用这个实现。")]
 #[allow(deprecated, unused)]
@@ -103,18 +133,28 @@ impl AudioBuffer<InputChannel<f64>> {
     }
 }
 
-impl<F: Clone> AudioBuffer<OutputChannel<F>> {
+impl<F: Copy> AudioBuffer<OutputChannel<F>> {
     pub fn process(&mut self) {
         for audio_port_buffer in &mut self.ports {
             audio_port_buffer.process();
         }
     }
+
+    /// 按端口顺序取走所有输出通道尚未发往Godot的音频数据。
+    pub fn take_channels(&mut self) -> Box<[Box<[F]>]> {
+        self.ports
+            .iter_mut()
+            .flat_map(|audio_port_buffer| audio_port_buffer.take_channels())
+            .collect()
+    }
 }
 impl AudioBuffer<OutputChannel<f32>> {
     pub fn new(
         host_shared: &HostShared,
         plugin_main_thread_handle: &mut PluginMainThreadHandle,
         plugin_audio_ports: &PluginAudioPorts,
+        frames_count: usize,
+        ring_buffer_frames_count: usize,
         audio_stream_generator: Gd<AudioStreamGenerator>,
     ) -> (Self, Box<[Gd<ClapOutputAudioPortAccess>]>) {
         let mut buffer = AudioPortInfoBuffer::new();
@@ -136,6 +176,8 @@ impl AudioBuffer<OutputChannel<f32>> {
 
                 let (port, clap_output_audio_channel_access) = Port::<OutputChannel<_>>::new(
                     audio_port_info.channel_count as usize,
+                    frames_count,
+                    ring_buffer_frames_count,
                     audio_stream_generator.clone(),
                 );
 
@@ -162,6 +204,20 @@ impl AudioBuffer<OutputChannel<f32>> {
                 .map(|port| port.pop_buffer(frames_count)),
         )
     }
+
+    /// 插件最近输出的`frames_count`帧是否全部为静音。
+    pub fn is_quiet(&self, frames_count: usize) -> bool {
+        self.ports.iter().all(|port| port.is_quiet(frames_count))
+    }
+
+    /// 将插件主输出端口（第一个输出端口）最近处理得到的音频写入`frames`，插件没有输出端口时写入静音。
+    pub fn copy_stereo(&self, frames: &mut [AudioFrame]) {
+        if let Some(port) = self.ports.first() {
+            port.copy_stereo(frames);
+        } else {
+            silence(frames);
+        }
+    }
 }
 #[deprecated(note = "在以Cardinal.clap进行开发测试时，发现f64将导致段错误。故不要使用这个实现。")]
 #[allow(deprecated, unused)]
@@ -210,6 +266,24 @@ fn assert_get_audio_port_info<'a>(
     Some(audio_port_info)
 }
 
+/// 幅度不超过这个值（约-120dB）的采样视为静音。
+const QUIET_AMPLITUDE: f32 = 1.0e-6;
+
+/// 判断一段采样是否全部为静音。
+pub fn is_quiet<'a>(samples: impl IntoIterator<Item = &'a f32>) -> bool {
+    samples
+        .into_iter()
+        .all(|sample| sample.abs() <= QUIET_AMPLITUDE)
+}
+
+/// 将音频帧全部置为静音。
+pub fn silence(frames: &mut [AudioFrame]) {
+    for frame in frames {
+        frame.left = 0.0;
+        frame.right = 0.0;
+    }
+}
+
 fn audio_prots(channel_counts: &[usize]) -> AudioPorts {
     AudioPorts::with_capacity(channel_counts.iter().sum(), channel_counts.len())
 }
@@ -1,19 +1,15 @@
 use crate::{
     clap_input_audio_channel_access::ClapInputAudioChannelAccess,
     clap_output_audio_channel_access::ClapOutputAudioChannelAccess,
+    host::audio_processor::audio_buffer::is_quiet,
+    ring_buffer::{Consumer, Producer, ring_buffer},
 };
 use godot::{classes::AudioStreamGenerator, prelude::*};
-use std::{
-    collections::VecDeque,
-    mem::swap,
-    sync::mpsc::{Receiver, Sender, channel},
-};
-
-// todo: 缓冲区使用一整块的内存或许性能会更好。
+use std::collections::VecDeque;
 
 pub struct InputChannel<F> {
-    /// 接受Godot输入的通道。
-    channel_buffer_rx: Receiver<Box<[F]>>,
+    /// 接受Godot输入的环形缓冲。
+    channel_buffer_rx: Consumer<F>,
     /// 缓冲区的帧大小，这个长度限制用于同步延迟，防止本应在卡顿下丢失的帧数据长期滞留在缓存中。
     frames_count: usize,
     /// 来自Godot，当前已接收并滞留的音频数据。
@@ -21,14 +17,19 @@ pub struct InputChannel<F> {
     /// 已经送往插件的音频数据。
     active_buffer: Vec<F>,
 }
-impl<F> InputChannel<F> {
+impl<F: Copy> InputChannel<F> {
     pub fn process(&mut self) {
-        let space_left = self.frames_count - self.buffer.len();
+        let space_left = self.frames_count.saturating_sub(self.buffer.len());
         self.buffer
-            .extend(self.channel_buffer_rx.try_iter().flatten().take(space_left))
+            .extend(self.channel_buffer_rx.pop_iter().take(space_left));
     }
-}
-impl<F: Clone> InputChannel<F> {
+
+    /// 丢弃所有尚未送往插件的音频数据，包括Godot端已经发出但还没有接收的。
+    pub fn clear(&mut self) {
+        self.channel_buffer_rx.clear();
+        self.buffer.clear();
+    }
+
     pub fn pop_buffer(
         &mut self,
         frames_count: usize,
@@ -44,8 +45,23 @@ impl<F: Clone> InputChannel<F> {
     }
 }
 impl InputChannel<f32> {
+    /// 接下来的`frames_count`帧是否全部为静音。
+    pub fn is_quiet(&self, frames_count: usize) -> bool {
+        is_quiet(self.buffer.iter().take(frames_count))
+    }
+
+    /// 将音频数据叠加到尚未送往插件的数据的开头，用于由外部直接送入、需要和本次处理对齐的音频。
+    pub fn mix(&mut self, frames: impl ExactSizeIterator<Item = f32>) {
+        if self.buffer.len() < frames.len() {
+            self.buffer.resize(frames.len(), 0.0);
+        }
+        for (sample, frame) in self.buffer.iter_mut().zip(frames) {
+            *sample += frame;
+        }
+    }
+
     pub fn new(frames_count: usize) -> (Self, Gd<ClapInputAudioChannelAccess>) {
-        let (channel_buffer_tx, channel_buffer_rx) = channel();
+        let (channel_buffer_tx, channel_buffer_rx) = ring_buffer(frames_count);
         (
             Self {
                 channel_buffer_rx,
@@ -61,21 +77,26 @@ impl InputChannel<f32> {
 pub struct OutputChannel<F> {
     /// 来自Clap插件，当前已接受并滞留的音频数据。
     buffer: Vec<F>,
-    /// 数据发往Godot的通道。
-    channel_buffer_tx: Sender<Box<[F]>>,
+    /// 数据发往Godot的环形缓冲。
+    channel_buffer_tx: Producer<F>,
 }
-impl<F> OutputChannel<F> {
+impl<F: Copy> OutputChannel<F> {
     pub fn process(&mut self) {
-        let mut buffer = Vec::new();
-        swap(&mut buffer, &mut self.buffer);
-        self.channel_buffer_tx
-            .send(buffer.into())
-            .unwrap_or_else(|err| {
-                panic!(
-                    "音频缓冲输出通道寄了，ClapPluginInstance大概已经被销毁，本缓冲所在的线程应该也会很快销毁：{:?}",
-                    err
-                )
-            })
+        // Godot端迟迟没有取走数据时，放不下的部分会被丢弃。
+        self.channel_buffer_tx.push_slice(&self.buffer);
+        self.buffer.clear();
+    }
+}
+impl<F> OutputChannel<F> {
+    /// 最近处理得到的`frames_count`帧音频数据，尚未发往Godot。
+    pub fn last_frames(&self, frames_count: usize) -> &[F] {
+        &self.buffer[self.buffer.len().saturating_sub(frames_count)..]
+    }
+
+    /// 取走所有尚未发往Godot的音频数据。
+    pub fn take_buffer(&mut self) -> Box<[F]> {
+        // 保留缓冲的容量，之后的实时处理就不需要重新分配内存。
+        self.buffer.drain(..).collect()
     }
 }
 impl<F: Clone> OutputChannel<F> {
@@ -86,13 +107,17 @@ impl<F: Clone> OutputChannel<F> {
     }
 }
 impl OutputChannel<f32> {
+    /// - `frames_count`: 缓冲区的帧大小。
+    /// - `ring_buffer_frames_count`: 发往Godot的环形缓冲的帧大小，需要能容纳Godot端两次取走数据之间产生的数据。
     pub fn new(
+        frames_count: usize,
+        ring_buffer_frames_count: usize,
         audio_stream_generator: Gd<AudioStreamGenerator>,
     ) -> (Self, Gd<ClapOutputAudioChannelAccess>) {
-        let (channel_buffer_tx, channel_buffer_rx) = channel();
+        let (channel_buffer_tx, channel_buffer_rx) = ring_buffer(ring_buffer_frames_count);
         (
             Self {
-                buffer: Vec::new(),
+                buffer: Vec::with_capacity(frames_count),
                 channel_buffer_tx,
             },
             ClapOutputAudioChannelAccess::new(channel_buffer_rx, audio_stream_generator),
@@ -1,22 +1,34 @@
 use crate::{
     clap_input_audio_port_access::ClapInputAudioPortAccess,
     clap_output_audio_port_access::ClapOutputAudioPortAccess,
-    host::audio_processor::audio_buffer::channel::{InputChannel, OutputChannel},
+    host::audio_processor::audio_buffer::{
+        channel::{InputChannel, OutputChannel},
+        is_quiet, silence,
+    },
 };
 use clack_host::prelude::AudioPortBufferType;
-use godot::{classes::AudioStreamGenerator, prelude::*};
+use godot::{
+    classes::{AudioStreamGenerator, native::AudioFrame},
+    prelude::*,
+};
 use std::{array::IntoIter, iter::repeat_n};
 
 pub struct Port<Channel> {
     channels: Box<[Channel]>,
 }
 
-impl<F> Port<InputChannel<F>> {
+impl<F: Copy> Port<InputChannel<F>> {
     pub fn process(&mut self) {
         for channel_buffer in &mut self.channels {
             channel_buffer.process();
         }
     }
+
+    pub fn clear(&mut self) {
+        for channel_buffer in &mut self.channels {
+            channel_buffer.clear();
+        }
+    }
 }
 impl Port<InputChannel<f32>> {
     pub fn new(
@@ -36,6 +48,31 @@ impl Port<InputChannel<f32>> {
         )
     }
 
+    /// 将各个通道的音频依次叠加到本端口的通道上，`channels`不够时剩下的通道不变。
+    pub fn mix_channels<'a>(&mut self, channels: &mut impl Iterator<Item = &'a [f32]>) {
+        for (channel, frames) in self.channels.iter_mut().zip(channels) {
+            channel.mix(frames.iter().copied());
+        }
+    }
+
+    pub fn is_quiet(&self, frames_count: usize) -> bool {
+        self.channels
+            .iter()
+            .all(|channel| channel.is_quiet(frames_count))
+    }
+
+    /// 将立体声音频叠加到本端口的前两个通道上，单通道的端口则叠加左右声道的均值。
+    pub fn mix_stereo(&mut self, frames: &[AudioFrame]) {
+        match &mut *self.channels {
+            [] => {}
+            [channel] => channel.mix(frames.iter().map(|frame| (frame.left + frame.right) / 2.0)),
+            [left_channel, right_channel, ..] => {
+                left_channel.mix(frames.iter().map(|frame| frame.left));
+                right_channel.mix(frames.iter().map(|frame| frame.right));
+            }
+        }
+    }
+
     pub fn pop_buffer(
         &mut self,
         frames_count: usize,
@@ -70,21 +107,36 @@ impl Port<InputChannel<f64>> {
     }
 }
 
-impl<F> Port<OutputChannel<F>> {
+impl<F: Copy> Port<OutputChannel<F>> {
     pub fn process(&mut self) {
         for channel_buffer in &mut self.channels {
             channel_buffer.process();
         }
     }
+
+    /// 取走各个通道尚未发往Godot的音频数据。
+    pub fn take_channels(&mut self) -> impl Iterator<Item = Box<[F]>> {
+        self.channels
+            .iter_mut()
+            .map(|channel_buffer| channel_buffer.take_buffer())
+    }
 }
 impl Port<OutputChannel<f32>> {
     pub fn new(
         channels_count: usize,
+        frames_count: usize,
+        ring_buffer_frames_count: usize,
         audio_stream_generator: Gd<AudioStreamGenerator>,
     ) -> (Self, Gd<ClapOutputAudioPortAccess>) {
         let (channels, clap_output_audio_channel_access): (Vec<_>, Vec<_>) =
             repeat_n((), channels_count)
-                .map(|()| OutputChannel::<f32>::new(audio_stream_generator.clone()))
+                .map(|()| {
+                    OutputChannel::<f32>::new(
+                        frames_count,
+                        ring_buffer_frames_count,
+                        audio_stream_generator.clone(),
+                    )
+                })
                 .unzip();
         (
             Self {
@@ -97,6 +149,28 @@ impl Port<OutputChannel<f32>> {
         )
     }
 
+    pub fn is_quiet(&self, frames_count: usize) -> bool {
+        self.channels
+            .iter()
+            .all(|channel| is_quiet(channel.last_frames(frames_count)))
+    }
+
+    /// 将本端口最近处理得到的音频写入`frames`，单通道的端口会同时写入左右声道。
+    pub fn copy_stereo(&self, frames: &mut [AudioFrame]) {
+        let Some(left_channel) = self.channels.first() else {
+            silence(frames);
+            return;
+        };
+        let right_channel = self.channels.get(1).unwrap_or(left_channel);
+
+        let left_frames = left_channel.last_frames(frames.len());
+        let right_frames = right_channel.last_frames(frames.len());
+        for (index, frame) in frames.iter_mut().enumerate() {
+            frame.left = left_frames.get(index).copied().unwrap_or_default();
+            frame.right = right_frames.get(index).copied().unwrap_or_default();
+        }
+    }
+
     pub fn pop_buffer(
         &mut self,
         frames_count: usize,
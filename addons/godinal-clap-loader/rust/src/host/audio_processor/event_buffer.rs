@@ -1,52 +1,538 @@
+use crate::{
+    midi::to_unknown,
+    ring_buffer::{Consumer, Producer},
+};
+use clack_extensions::params::ParamClearFlags;
 use clack_host::{
-    events::UnknownEvent,
+    events::{EventHeader, UnknownEvent, event_types::MidiSysExEvent, spaces::CoreEventSpace},
     prelude::{InputEvents, OutputEvents},
+    utils::ClapId,
 };
-use std::sync::mpsc::{Receiver, Sender};
 
 pub struct EventBuffer<Handle> {
     event_buffer: clack_host::prelude::EventBuffer,
     handle: Handle,
 }
 impl<Handle> EventBuffer<Handle> {
-    pub fn new(handle: Handle) -> Self {
+    /// - `events_capacity`: 预先分配的事件个数，在此之内音频线程不需要分配内存。
+    pub fn new(handle: Handle, events_capacity: usize) -> Self {
         Self {
-            event_buffer: clack_host::prelude::EventBuffer::new(),
+            event_buffer: clack_host::prelude::EventBuffer::with_capacity(events_capacity),
             handle,
         }
     }
 }
 
-pub type InputHandle = Receiver<Box<[Box<UnknownEvent>]>>;
+/// 事件的调度时间。
+#[derive(Clone, Copy, Debug)]
+pub enum EventTime {
+    /// 相对于音频线程下一次处理开始时的帧偏移。
+    FrameOffset(u32),
+    /// 插件的稳定时间，即插件自开始处理起已处理的总帧数。
+    SteadyTime(u64),
+}
+
+/// 等待送往插件的输入事件。
+pub struct ScheduledEvent {
+    time: EventTime,
+    event: Box<UnknownEvent>,
+    /// 事件通过指针引用的数据，例如系统独占消息的字节，需要一直存活到事件送达插件为止。
+    payload: Option<Box<[u8]>>,
+}
+impl ScheduledEvent {
+    pub fn new(time: EventTime, event: Box<UnknownEvent>) -> Self {
+        Self {
+            time,
+            event,
+            payload: None,
+        }
+    }
+
+    /// 系统独占事件。
+    /// - `sys_ex`: 完整的系统独占消息，包括开头的`0xF0`和结尾的`0xF7`。
+    pub fn sys_ex(time: EventTime, port_index: u16, sys_ex: Box<[u8]>) -> Self {
+        // 事件只记录了数据的地址，数据本身在堆上，随本结构移动时地址不会改变。
+        let event = to_unknown(MidiSysExEvent::new(0, port_index, &sys_ex));
+        Self {
+            time,
+            event,
+            payload: Some(sys_ex),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn event(&self) -> &UnknownEvent {
+        &self.event
+    }
+
+    #[cfg(test)]
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.payload.as_deref()
+    }
+}
+impl From<Box<UnknownEvent>> for ScheduledEvent {
+    /// 以事件头中的时间作为帧偏移。
+    fn from(event: Box<UnknownEvent>) -> Self {
+        Self::new(EventTime::FrameOffset(event.header().time()), event)
+    }
+}
+
+/// 事件的最大字节数，足以容纳所有的Clap核心事件。
+const MAX_EVENT_LENGTH: usize = 128;
+
+/// 以固定大小的字节保存的事件，满足Clap事件的对齐要求，可以直接在环形缓冲和等待队列中移动。
+#[derive(Clone, Copy)]
+#[repr(C, align(8))]
+pub struct EventBytes([u8; MAX_EVENT_LENGTH]);
+impl EventBytes {
+    /// 返回：事件超过[`MAX_EVENT_LENGTH`]字节时为空。
+    fn new(event: &UnknownEvent) -> Option<Self> {
+        Self::from_bytes(event.as_bytes())
+    }
+
+    /// 从一个完整事件的字节复制，这些字节本身不需要满足对齐要求。
+    ///
+    /// 返回：字节不足一个事件头、与事件头中的长度不符或超过[`MAX_EVENT_LENGTH`]字节时为空。
+    pub fn from_bytes(event: &[u8]) -> Option<Self> {
+        let length = u32::from_ne_bytes(event.get(..size_of::<u32>())?.try_into().ok()?);
+        if event.len() < size_of::<EventHeader>() || length as usize != event.len() {
+            return None;
+        }
+        let mut bytes = [0; MAX_EVENT_LENGTH];
+        bytes.get_mut(..event.len())?.copy_from_slice(event);
+        Some(Self(bytes))
+    }
+
+    pub fn event(&self) -> &UnknownEvent {
+        // 事件头以`u32`的事件长度开头。
+        let mut length = [0; size_of::<u32>()];
+        length.copy_from_slice(&self.0[..size_of::<u32>()]);
+        // SAFETY: 字节复制自一个完整的事件，且满足事件的对齐要求。
+        unsafe {
+            UnknownEvent::from_bytes_unchecked(&self.0[..u32::from_ne_bytes(length) as usize])
+        }
+    }
+
+    /// 改写事件头中的时间。
+    fn set_time(&mut self, time: u32) {
+        // SAFETY: 所有的Clap事件都以事件头开头，且这里持有该事件的独占引用。
+        let header = self.0.as_mut_ptr() as *mut EventHeader;
+        unsafe { (*header).set_time(time) }
+    }
+}
+
+/// 经由环形缓冲送往音频线程的输入事件，见[`ScheduledEvent`]。
+///
+/// 事件本身以固定大小的字节送去，所引用的数据则连同所有权一起送去，插件处理完之后再交还主线程释放，
+/// 这样音频线程既不需要分配内存，也不需要释放内存。
+pub struct InputEvent {
+    time: EventTime,
+    event: EventBytes,
+    payload: Option<Box<[u8]>>,
+}
+impl InputEvent {
+    /// 返回：事件超过[`MAX_EVENT_LENGTH`]字节时为空。
+    pub fn new(scheduled_event: ScheduledEvent) -> Option<Self> {
+        Some(Self {
+            time: scheduled_event.time,
+            event: EventBytes::new(&scheduled_event.event)?,
+            payload: scheduled_event.payload,
+        })
+    }
+}
+
+/// 尚未生效的事件。
+struct PendingEvent {
+    /// 事件生效的稳定时间。
+    steady_time: u64,
+    event: EventBytes,
+    payload: Option<Box<[u8]>>,
+}
+
+pub struct InputHandle {
+    /// 接受Godot输入的环形缓冲。
+    input_events_rx: Consumer<InputEvent>,
+    /// 接受需要清除的参数编号及清除标志的环形缓冲，这些参数尚未生效的、对应类型的事件会被丢弃。
+    cleared_params_rx: Consumer<(ClapId, ParamClearFlags)>,
+    /// 将插件处理完的事件所引用的数据交还主线程释放的环形缓冲。
+    released_payloads_tx: Producer<Box<[u8]>>,
+    /// 尚未生效的事件，按生效时间升序排列。
+    ///
+    /// 容量是预先分配的，已满时新的事件会留在环形缓冲中，等到有空位时再接收。
+    pending_events: Vec<PendingEvent>,
+    /// 已经送往插件的事件所引用的数据，插件处理完这些事件之前不能释放。
+    active_payloads: Vec<Box<[u8]>>,
+}
+impl InputHandle {
+    pub fn new(
+        input_events_rx: Consumer<InputEvent>,
+        cleared_params_rx: Consumer<(ClapId, ParamClearFlags)>,
+        released_payloads_tx: Producer<Box<[u8]>>,
+        events_capacity: usize,
+    ) -> Self {
+        Self {
+            input_events_rx,
+            cleared_params_rx,
+            released_payloads_tx,
+            pending_events: Vec::with_capacity(events_capacity),
+            active_payloads: Vec::with_capacity(events_capacity),
+        }
+    }
+
+    /// 将插件已经处理完的事件所引用的数据交还主线程。
+    ///
+    /// 主线程迟迟没有取走时，交还不了的数据留到下一次再交还。
+    fn release_payloads(&mut self) {
+        while let Some(payload) = self.active_payloads.pop() {
+            if let Err(payload) = self.released_payloads_tx.push(payload) {
+                self.active_payloads.push(payload);
+                return;
+            }
+        }
+    }
+}
 impl EventBuffer<InputHandle> {
-    pub fn pop_buffer(&mut self) -> InputEvents<'_> {
+    /// 接收来自Godot的事件，并按生效时间排入等待队列。
+    /// - `steady_time`: 即将开始的这次处理的稳定时间，用于换算以帧偏移调度的事件。
+    pub fn process(&mut self, steady_time: u64) {
+        let pending_events = &mut self.handle.pending_events;
+        while pending_events.len() < pending_events.capacity() {
+            let Some(input_event) = self.handle.input_events_rx.pop() else {
+                break;
+            };
+            insert_pending_event(pending_events, steady_time, input_event);
+        }
+
+        for (param_id, flags) in self.handle.cleared_params_rx.pop_iter() {
+            pending_events.retain(|pending_event| {
+                !is_cleared_param_event(pending_event.event.event(), param_id, flags)
+            });
+        }
+    }
+
+    /// 直接将事件排入等待队列，不经过来自Godot的通道，用于离线渲染。
+    ///
+    /// 离线渲染没有实时性的要求，事件过多时会扩容等待队列。
+    /// - `steady_time`: 用于换算以帧偏移调度的事件的稳定时间。
+    pub fn schedule(&mut self, steady_time: u64, scheduled_events: Box<[ScheduledEvent]>) {
+        for input_event in scheduled_events.into_iter().filter_map(InputEvent::new) {
+            insert_pending_event(&mut self.handle.pending_events, steady_time, input_event);
+        }
+    }
+
+    /// 丢弃所有尚未生效的事件，包括Godot端已经发出但还没有接收的。
+    pub fn clear(&mut self, steady_time: u64) {
+        loop {
+            self.process(steady_time);
+            let payloads = self
+                .handle
+                .pending_events
+                .drain(..)
+                .filter_map(|pending_event| pending_event.payload);
+            self.handle.active_payloads.extend(payloads);
+            if self.handle.input_events_rx.is_empty() {
+                break;
+            }
+        }
+        self.handle.release_payloads();
+    }
+
+    /// 在`[steady_time, steady_time + frames_count)`这段时间内是否有需要生效的事件，已经过期的事件也算在内。
+    pub fn has_due_events(&self, steady_time: u64, frames_count: usize) -> bool {
+        self.handle
+            .pending_events
+            .first()
+            .is_some_and(|pending_event| {
+                pending_event.steady_time < steady_time + frames_count as u64
+            })
+    }
+
+    /// 取出在`[steady_time, steady_time + frames_count)`这段时间内生效的事件。
+    ///
+    /// 事件头中的时间会被改写为相对于本次处理开始时的帧偏移，已经过期的事件会在本次处理的开头生效。
+    pub fn pop_buffer(&mut self, steady_time: u64, frames_count: usize) -> InputEvents<'_> {
         self.event_buffer.clear();
-        for event in self.handle.try_iter().flatten() {
-            self.event_buffer.push(&event);
+
+        let end_steady_time = steady_time + frames_count as u64;
+        let due_events_count = self
+            .handle
+            .pending_events
+            .partition_point(|pending_event| pending_event.steady_time < end_steady_time);
+        // 上一次处理的事件已经处理完了，它们引用的数据可以交还主线程释放了。
+        self.handle.release_payloads();
+        for mut pending_event in self.handle.pending_events.drain(..due_events_count) {
+            pending_event
+                .event
+                .set_time(pending_event.steady_time.saturating_sub(steady_time) as u32);
+            self.event_buffer.push(pending_event.event.event());
+            self.handle.active_payloads.extend(pending_event.payload);
         }
 
         self.event_buffer.as_input()
     }
 }
 
-pub type OutputHandle = Sender<Box<[Box<UnknownEvent>]>>;
+/// 发往Godot的事件以字节的形式写入环形缓冲。
+///
+/// 每个事件前都带有以本机字节序表示的`u32`长度和`u64`稳定时间，稳定时间即事件在插件时间轴上发生的时刻。
+/// 事件之后紧跟着同样带有`u32`长度的、事件所引用的数据：插件的系统独占消息只在本次处理期间有效，需要当场复制下来。
+pub type OutputHandle = Producer<u8>;
 impl EventBuffer<OutputHandle> {
-    pub fn process(&mut self) {
-        let events = self.event_buffer.iter().map(|event| {
-            let event = event.as_bytes().to_vec().into_boxed_slice();
-            let event = Box::into_raw(event);
-            unsafe {
-                let event = UnknownEvent::from_bytes_unchecked(&*event) as *const UnknownEvent
-                    as *mut UnknownEvent;
-                Box::from_raw(event as *mut UnknownEvent)
-            }
-        });
-        self.handle.send(events.collect()).unwrap_or_else(|err|
-            panic!(
-                "音频事件缓冲输出通道寄了，ClapPluginInstance大概已经被销毁，本缓冲所在的线程应该也会很快销毁：{:?}", err) );
+    /// 将插件输出的事件发往Godot。
+    /// - `steady_time`: 产生这些事件的那次处理开始时的稳定时间，加上事件头中的帧偏移即为事件的稳定时间。
+    pub fn process(&mut self, steady_time: u64) {
+        for event in self.event_buffer.iter() {
+            let event_steady_time = (steady_time + event.header().time() as u64).to_ne_bytes();
+            let payload = match event.as_core_event() {
+                Some(CoreEventSpace::MidiSysEx(midi_sys_ex_event)) => midi_sys_ex_event.data(),
+                _ => &[],
+            };
+            let payload_length = (payload.len() as u32).to_ne_bytes();
+            let event = event.as_bytes();
+            let event_length = (event.len() as u32).to_ne_bytes();
+            // Godot端迟迟没有取走事件时，放不下的事件会被丢弃。
+            self.handle.push_slices(&[
+                &event_length[..],
+                &event_steady_time[..],
+                event,
+                &payload_length[..],
+                payload,
+            ]);
+        }
         self.event_buffer.clear();
     }
     pub fn pop_buffer(&mut self) -> OutputEvents<'_> {
         self.event_buffer.as_output()
     }
 }
+
+/// 将事件按生效时间插入等待队列。
+/// - `steady_time`: 用于换算以帧偏移调度的事件的稳定时间。
+fn insert_pending_event(
+    pending_events: &mut Vec<PendingEvent>,
+    steady_time: u64,
+    input_event: InputEvent,
+) {
+    let event_steady_time = match input_event.time {
+        EventTime::FrameOffset(frame_offset) => steady_time + frame_offset as u64,
+        EventTime::SteadyTime(event_steady_time) => event_steady_time,
+    };
+    // 插到同一时间的事件之后，保证同时生效的事件维持发送时的顺序。
+    let index = pending_events
+        .partition_point(|pending_event| pending_event.steady_time <= event_steady_time);
+    pending_events.insert(
+        index,
+        PendingEvent {
+            steady_time: event_steady_time,
+            event: input_event.event,
+            payload: input_event.payload,
+        },
+    );
+}
+
+/// 事件是否是按清除标志需要清除的参数事件。
+///
+/// [`ParamClearFlags::AUTOMATIONS`]清除参数值事件，[`ParamClearFlags::MODULATIONS`]清除调制事件，
+/// [`ParamClearFlags::ALL`]两者都清除。
+fn is_cleared_param_event(event: &UnknownEvent, param_id: ClapId, flags: ParamClearFlags) -> bool {
+    match event.as_core_event() {
+        Some(CoreEventSpace::ParamValue(param_value_event)) => {
+            param_value_event.param_id() == Some(param_id)
+                && flags.intersects(ParamClearFlags::ALL | ParamClearFlags::AUTOMATIONS)
+        }
+        Some(CoreEventSpace::ParamMod(param_mod_event)) => {
+            param_mod_event.param_id() == Some(param_id)
+                && flags.intersects(ParamClearFlags::ALL | ParamClearFlags::MODULATIONS)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring_buffer::ring_buffer;
+    use clack_host::{
+        events::{
+            Pckn,
+            event_types::{ParamModEvent, ParamValueEvent},
+        },
+        utils::Cookie,
+    };
+
+    struct TestInput {
+        input_events_tx: Producer<InputEvent>,
+        cleared_params_tx: Producer<(ClapId, ParamClearFlags)>,
+        event_buffer: EventBuffer<InputHandle>,
+    }
+    impl TestInput {
+        fn new() -> Self {
+            let (input_events_tx, input_events_rx) = ring_buffer(16);
+            let (cleared_params_tx, cleared_params_rx) = ring_buffer(4);
+            let (released_payloads_tx, _) = ring_buffer(16);
+            Self {
+                input_events_tx,
+                cleared_params_tx,
+                event_buffer: EventBuffer::new(
+                    InputHandle::new(input_events_rx, cleared_params_rx, released_payloads_tx, 16),
+                    16,
+                ),
+            }
+        }
+
+        fn send(&mut self, time: EventTime, event: Box<UnknownEvent>) {
+            let input_event = InputEvent::new(ScheduledEvent::new(time, event)).unwrap();
+            assert!(self.input_events_tx.push(input_event).is_ok());
+        }
+
+        /// 取出一次处理的事件，每个事件记为（帧偏移，类型，参数编号，值）。
+        fn pop(
+            &mut self,
+            steady_time: u64,
+            frames_count: usize,
+        ) -> Vec<(u32, &'static str, u32, f64)> {
+            self.event_buffer
+                .pop_buffer(steady_time, frames_count)
+                .iter()
+                .map(|event| {
+                    let time = event.header().time();
+                    match event.as_core_event() {
+                        Some(CoreEventSpace::ParamValue(param_value_event)) => (
+                            time,
+                            "value",
+                            param_value_event.param_id().unwrap().get(),
+                            param_value_event.value(),
+                        ),
+                        Some(CoreEventSpace::ParamMod(param_mod_event)) => (
+                            time,
+                            "mod",
+                            param_mod_event.param_id().unwrap().get(),
+                            param_mod_event.amount(),
+                        ),
+                        _ => panic!("意外的事件"),
+                    }
+                })
+                .collect()
+        }
+    }
+
+    fn param_id(id: u32) -> ClapId {
+        ClapId::from_raw(id).unwrap()
+    }
+
+    fn param_value(id: u32, value: f64) -> Box<UnknownEvent> {
+        to_unknown(ParamValueEvent::new(
+            0,
+            param_id(id),
+            Pckn::match_all(),
+            value,
+            Cookie::empty(),
+        ))
+    }
+
+    fn param_mod(id: u32, amount: f64) -> Box<UnknownEvent> {
+        to_unknown(ParamModEvent::new(
+            0,
+            param_id(id),
+            Pckn::match_all(),
+            amount,
+            Cookie::empty(),
+        ))
+    }
+
+    #[test]
+    fn same_time_events_keep_sending_order() {
+        let mut input = TestInput::new();
+        for value in [1.0, 2.0, 3.0] {
+            input.send(EventTime::SteadyTime(10), param_value(1, value));
+        }
+        input.send(EventTime::SteadyTime(5), param_value(1, 0.0));
+        input.event_buffer.process(0);
+        assert_eq!(
+            input.pop(0, 16),
+            [
+                (5, "value", 1, 0.0),
+                (10, "value", 1, 1.0),
+                (10, "value", 1, 2.0),
+                (10, "value", 1, 3.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn overdue_events_take_effect_at_offset_zero() {
+        let mut input = TestInput::new();
+        input.send(EventTime::SteadyTime(40), param_value(1, 1.0));
+        input.event_buffer.process(100);
+        assert!(input.event_buffer.has_due_events(100, 8));
+        assert_eq!(input.pop(100, 8), [(0, "value", 1, 1.0)]);
+    }
+
+    #[test]
+    fn frame_offsets_are_relative_to_receiving_steady_time() {
+        let mut input = TestInput::new();
+        input.send(EventTime::SteadyTime(1005), param_value(1, 2.0));
+        input.send(EventTime::FrameOffset(3), param_value(1, 1.0));
+        input.event_buffer.process(1000);
+        assert_eq!(
+            input.pop(1000, 8),
+            [(3, "value", 1, 1.0), (5, "value", 1, 2.0)]
+        );
+
+        // 帧偏移按接收时的稳定时间换算，接收之后才处理时就已经过期了。
+        input.send(EventTime::FrameOffset(3), param_value(1, 3.0));
+        input.event_buffer.process(1008);
+        assert_eq!(input.pop(1016, 8), [(0, "value", 1, 3.0)]);
+    }
+
+    #[test]
+    fn events_are_split_at_chunk_boundaries() {
+        let mut input = TestInput::new();
+        for steady_time in [0, 7, 8, 15, 16] {
+            input.send(
+                EventTime::SteadyTime(steady_time),
+                param_value(1, steady_time as f64),
+            );
+        }
+        input.event_buffer.process(0);
+
+        assert!(input.event_buffer.has_due_events(0, 8));
+        assert_eq!(
+            input.pop(0, 8),
+            [(0, "value", 1, 0.0), (7, "value", 1, 7.0)]
+        );
+        assert!(input.event_buffer.has_due_events(8, 8));
+        assert_eq!(
+            input.pop(8, 8),
+            [(0, "value", 1, 8.0), (7, "value", 1, 15.0)]
+        );
+        assert!(!input.event_buffer.has_due_events(16, 0));
+        assert!(input.event_buffer.has_due_events(16, 1));
+        assert_eq!(input.pop(16, 8), [(0, "value", 1, 16.0)]);
+        assert!(!input.event_buffer.has_due_events(24, 8));
+        assert!(input.pop(24, 8).is_empty());
+    }
+
+    #[test]
+    fn clear_flags_select_event_types() {
+        for (flags, expected) in [
+            (
+                ParamClearFlags::AUTOMATIONS,
+                vec![(0, "mod", 1, 0.5), (0, "value", 2, 1.0)],
+            ),
+            (
+                ParamClearFlags::MODULATIONS,
+                vec![(0, "value", 1, 1.0), (0, "value", 2, 1.0)],
+            ),
+            (ParamClearFlags::ALL, vec![(0, "value", 2, 1.0)]),
+        ] {
+            let mut input = TestInput::new();
+            input.send(EventTime::FrameOffset(0), param_value(1, 1.0));
+            input.send(EventTime::FrameOffset(0), param_mod(1, 0.5));
+            input.send(EventTime::FrameOffset(0), param_value(2, 1.0));
+            assert!(input.cleared_params_tx.push((param_id(1), flags)).is_ok());
+            input.event_buffer.process(0);
+            assert_eq!(input.pop(0, 8), expected, "清除标志{}", flags.bits());
+        }
+    }
+}
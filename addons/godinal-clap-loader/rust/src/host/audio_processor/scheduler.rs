@@ -0,0 +1,95 @@
+use std::{
+    sync::{
+        Condvar, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+/// 音频线程的调度器。
+///
+/// 音频线程在两次处理之间会睡眠到下一次处理的截止时间，Godot端发送了需要尽快送达插件的事件时可以提前唤醒它。
+///
+/// 同时记录插件的睡眠状态，插件可以通过`request_process`从任意线程将自己唤醒。
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    condvar: Condvar,
+    /// 插件是否正在睡眠。睡眠期间插件处于停止处理的状态，直到有新的输入音频或事件，或者插件请求处理为止。
+    is_sleeping: AtomicBool,
+    /// 插件从睡眠中被唤醒的次数，Godot端据此得知插件醒过来了。
+    wake_ups_count: AtomicU64,
+}
+#[derive(Default)]
+struct SchedulerState {
+    /// Godot端请求音频线程尽快进行一次处理。
+    wake_requested: bool,
+    /// Godot端请求音频线程停止处理并退出。
+    stop_requested: bool,
+}
+impl Scheduler {
+    fn state(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 唤醒音频线程，让其尽快进行一次处理。
+    pub fn wake(&self) {
+        self.state().wake_requested = true;
+        self.condvar.notify_one();
+    }
+
+    /// 请求音频线程停止处理并退出。
+    pub fn request_stop(&self) {
+        self.state().stop_requested = true;
+        self.condvar.notify_one();
+    }
+
+    /// 标记插件进入睡眠，需要在音频线程上停止插件的处理之后调用。
+    pub fn sleep(&self) {
+        self.is_sleeping.store(true, Ordering::Release);
+    }
+
+    /// 清除插件的睡眠标记，插件会在下一次处理前重新开始处理。
+    ///
+    /// 返回：插件之前是否正在睡眠。
+    pub fn wake_up(&self) -> bool {
+        let was_sleeping = self.is_sleeping.swap(false, Ordering::AcqRel);
+        if was_sleeping {
+            self.wake_ups_count.fetch_add(1, Ordering::Release);
+        }
+        was_sleeping
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.is_sleeping.load(Ordering::Acquire)
+    }
+
+    pub fn wake_ups_count(&self) -> u64 {
+        self.wake_ups_count.load(Ordering::Acquire)
+    }
+
+    /// 在音频线程上睡眠，直到`deadline`或被唤醒。
+    ///
+    /// 返回：音频线程是否应该继续处理，请求了停止时为`false`。停止请求会被消耗掉，以便插件重启后重新运行音频线程。
+    pub fn wait_until(&self, deadline: Instant) -> bool {
+        let mut state = self.state();
+        loop {
+            if state.stop_requested {
+                state.stop_requested = false;
+                return false;
+            }
+            if state.wake_requested {
+                state.wake_requested = false;
+                return true;
+            }
+            let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                return true;
+            };
+            state = self
+                .condvar
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
@@ -10,7 +10,8 @@ use crate::host::{
     },
 };
 use clack_extensions::{
-    gui::HostGui, log::HostLog, params::HostParams, state::HostState, timer::HostTimer,
+    gui::HostGui, latency::HostLatency, log::HostLog, note_ports::HostNotePorts,
+    params::HostParams, state::HostState, tail::HostTail, timer::HostTimer,
 };
 use clack_host::host::{HostExtensions, HostHandlers};
 
@@ -20,7 +21,7 @@ impl HostHandlers for Host {
     type AudioProcessor<'a> = HostAudioProcessor<'a>;
 
     /// 声明本主机侧实现了哪些句柄可供插件使用。
-    /// 声明之后均需要在 [`HostShared`] 或 [`HostMainThread`] 中实现。
+    /// 声明之后均需要在 [`HostShared`]、[`HostMainThread`] 或 [`HostAudioProcessor`] 中实现。
     #[allow(unused)]
     fn declare_extensions(builder: &mut HostExtensions<Self>, shared: &Self::Shared<'_>) {
         builder
@@ -28,6 +29,9 @@ impl HostHandlers for Host {
             .register::<HostGui>()
             .register::<HostTimer>()
             .register::<HostParams>()
-            .register::<HostState>();
+            .register::<HostState>()
+            .register::<HostLatency>()
+            .register::<HostNotePorts>()
+            .register::<HostTail>();
     }
 }
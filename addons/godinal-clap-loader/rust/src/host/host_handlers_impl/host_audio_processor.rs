@@ -1,4 +1,5 @@
 use crate::host::host_handlers_impl::{host_main_thread::HostMainThread, host_shared::HostShared};
+use clack_extensions::tail::HostTailImpl;
 use clack_host::host::AudioProcessorHandler;
 
 /// 用于为启动音频处理实例提供支持。
@@ -16,3 +17,7 @@ impl<'a> HostAudioProcessor<'a> {
     }
 }
 impl<'a> AudioProcessorHandler<'a> for HostAudioProcessor<'a> {}
+impl<'a> HostTailImpl for HostAudioProcessor<'a> {
+    /// 音频线程每次有输入时都会重新查询拖尾长度，所以这里不需要额外的处理。
+    fn changed(&mut self) {}
+}
@@ -1,5 +1,10 @@
-use crate::host::host_handlers_impl::host_shared::HostShared;
+use crate::host::{
+    host_handlers_impl::host_shared::HostShared,
+    plugin_message::{PluginMessage, PluginParamsMessage},
+};
 use clack_extensions::{
+    latency::HostLatencyImpl,
+    note_ports::{HostNotePortsImpl, NoteDialects, NotePortRescanFlags},
     params::{HostParamsImplMainThread, ParamClearFlags, ParamRescanFlags},
     state::HostStateImpl,
     timer::{HostTimerImpl, TimerId},
@@ -55,21 +60,32 @@ impl<'a> HostTimerImpl for HostMainThread<'a> {
 }
 impl<'a> HostParamsImplMainThread for HostMainThread<'a> {
     fn rescan(&mut self, flags: ParamRescanFlags) {
-        for flag in flags.iter() {
-            match flag {
-                ParamRescanFlags::VALUES
-                | ParamRescanFlags::INFO
-                | ParamRescanFlags::TEXT
-                | ParamRescanFlags::ALL => {
-                    //todo: 大概需要通知外层宿主重新扫描所有的Param参数控制。
-                }
-                flag => unreachable!("未处理的标志：{:?}", flag),
-            }
-        }
+        // 插件可能在主机调用插件的过程中调用本方法，所以转交给主机在主循环中处理。
+        self.host_shared
+            .send(PluginMessage::Params(PluginParamsMessage::Rescan(flags)));
     }
 
     fn clear(&mut self, param_id: ClapId, flags: ParamClearFlags) {
-        todo!()
+        self.host_shared
+            .send(PluginMessage::Params(PluginParamsMessage::Clear(
+                param_id, flags,
+            )));
+    }
+}
+impl<'a> HostLatencyImpl for HostMainThread<'a> {
+    fn changed(&mut self) {
+        // 插件通常在激活过程中调用本方法，此时还不能查询延迟，所以交给主机在主循环中处理。
+        self.host_shared.send(PluginMessage::LatencyChanged);
+    }
+}
+impl<'a> HostNotePortsImpl for HostMainThread<'a> {
+    /// 原始midi和midi 2.0事件都会原样转交给插件，所以这三种方言主机都支持。
+    fn supported_dialects(&self) -> NoteDialects {
+        NoteDialects::CLAP | NoteDialects::MIDI | NoteDialects::MIDI2
+    }
+
+    fn rescan(&mut self, _flags: NotePortRescanFlags) {
+        self.host_shared.send(PluginMessage::NotePortsRescanned);
     }
 }
 impl<'a> HostStateImpl for HostMainThread<'a> {
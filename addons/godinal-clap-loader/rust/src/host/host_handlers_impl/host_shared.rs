@@ -1,4 +1,7 @@
-use crate::host::plugin_message::{PluginGuiMessage, PluginMessage};
+use crate::host::{
+    audio_processor::scheduler::Scheduler,
+    plugin_message::{PluginGuiMessage, PluginMessage, PluginParamsMessage},
+};
 use clack_extensions::{
     gui::{GuiSize, HostGuiImpl},
     log::{HostLogImpl, LogSeverity},
@@ -6,17 +9,26 @@ use clack_extensions::{
 };
 use clack_host::host::{HostError, SharedHandler};
 use godot::prelude::*;
-use std::sync::mpsc::Sender;
+use std::sync::{Arc, mpsc::Sender};
 
 /// 插件消息转发器，接受来自插件的回调，将其转换为[`通道消息`](PluginMessage)转发给主机，
 /// 这样就可以让主机自行决定在什么合适的时机去处理这些消息。
 #[derive(Clone)]
 pub struct HostShared {
     plugin_tx: Sender<PluginMessage>,
+    /// 音频线程的调度器，插件请求处理时用来将其从睡眠中唤醒。
+    scheduler: Arc<Scheduler>,
 }
 impl HostShared {
     pub fn new(plugin_tx: Sender<PluginMessage>) -> Self {
-        Self { plugin_tx }
+        Self {
+            plugin_tx,
+            scheduler: Arc::new(Scheduler::default()),
+        }
+    }
+
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
     }
 
     pub fn send(&self, plugin_message: PluginMessage) {
@@ -27,11 +39,12 @@ impl HostShared {
 }
 impl<'a> SharedHandler<'a> for HostShared {
     fn request_restart(&self) {
-        todo!()
+        self.send(PluginMessage::RequestRestart);
     }
 
     fn request_process(&self) {
-        todo!()
+        self.scheduler.wake_up();
+        self.scheduler.wake();
     }
 
     fn request_callback(&self) {
@@ -87,6 +100,6 @@ impl HostGuiImpl for HostShared {
 }
 impl HostParamsImplShared for HostShared {
     fn request_flush(&self) {
-        todo!()
+        self.send(PluginMessage::Params(PluginParamsMessage::RequestFlush));
     }
 }
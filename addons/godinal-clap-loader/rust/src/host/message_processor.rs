@@ -37,8 +37,17 @@ impl MessageProcessor<Host> {
         }
     }
 
-    pub fn process(&mut self) {
+    /// 处理插件消息。
+    ///
+    /// 返回：需要交给主机自行处理的消息，见[`PluginMessage::is_for_host`]。
+    pub fn process(&mut self) -> Vec<PluginMessage> {
+        let mut host_messages = Vec::new();
         while let Ok(message) = self.plugin_rx.try_recv() {
+            if message.is_for_host() {
+                host_messages.push(message);
+                continue;
+            }
+
             let message_process_result = self.message_processor_impl.process(message);
             if let Err(host_process_error) = message_process_result {
                 self.plugin_instance().access_shared_handler(|host_shared| {
@@ -49,6 +58,7 @@ impl MessageProcessor<Host> {
                 })
             }
         }
+        host_messages
     }
 }
 impl<T: HostHandlers> Deref for MessageProcessor<T> {
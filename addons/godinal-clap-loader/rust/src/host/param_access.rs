@@ -0,0 +1,199 @@
+use crate::{clap_param_info::ClapParamInfo, host::host_handlers_impl::host_shared::HostShared};
+use clack_extensions::{
+    log::{HostLogImpl, LogSeverity},
+    params::{ParamInfo, ParamInfoBuffer, PluginParams},
+};
+use clack_host::{plugin::PluginMainThreadHandle, utils::ClapId};
+use godot::prelude::*;
+use std::{cell::Cell, collections::HashSet, ffi::CString, mem::MaybeUninit};
+
+/// 插件参数的访问句柄，缓存了插件所有参数的描述信息。
+pub struct ParamAccess {
+    /// 插件侧的参数功能句柄，插件不支持参数功能时为空。
+    plugin_params: Option<PluginParams>,
+    /// 插件参数描述信息的缓存。
+    param_infos: Box<[Gd<ClapParamInfo>]>,
+    /// 插件参数值的缓存，和[`Self::param_infos`]一一对应。
+    /// 用于在无法访问插件主线程句柄的地方（例如Godot读取属性时）读取参数值。
+    param_values: Box<[Cell<f64>]>,
+}
+impl ParamAccess {
+    pub fn new(
+        host_shared: &HostShared,
+        plugin_main_thread_handle: &mut PluginMainThreadHandle,
+    ) -> Self {
+        let mut param_access = Self {
+            plugin_params: plugin_main_thread_handle.get_extension::<PluginParams>(),
+            param_infos: Box::new([]),
+            param_values: Box::new([]),
+        };
+        param_access.rescan(host_shared, plugin_main_thread_handle);
+        param_access
+    }
+
+    /// 重新从插件读取所有参数的描述信息和值。
+    pub fn rescan(
+        &mut self,
+        host_shared: &HostShared,
+        plugin_main_thread_handle: &mut PluginMainThreadHandle,
+    ) {
+        let Some(plugin_params) = self.plugin_params else {
+            return;
+        };
+
+        let mut buffer = ParamInfoBuffer::new();
+        let mut property_names = HashSet::new();
+        let params_count = plugin_params.count(plugin_main_thread_handle);
+        self.param_infos = (0..params_count)
+            .filter_map(|index| {
+                let Some(param_info) =
+                    plugin_params.get_info(plugin_main_thread_handle, index, &mut buffer)
+                else {
+                    host_shared.log(
+                        LogSeverity::PluginMisbehaving,
+                        &format!("获取序号为{index}的插件参数描述信息失败"),
+                    );
+                    return None;
+                };
+                let property_name = unique_property_name(&param_info, &mut property_names);
+                Some(ClapParamInfo::new(&param_info, property_name))
+            })
+            .collect();
+        self.param_values = self
+            .param_infos
+            .iter()
+            .map(|param_info| Cell::new(param_info.bind().default_value()))
+            .collect();
+        self.refresh_values(plugin_main_thread_handle);
+    }
+
+    /// 重新从插件读取所有参数的值。
+    pub fn refresh_values(&self, plugin_main_thread_handle: &mut PluginMainThreadHandle) {
+        let Some(plugin_params) = self.plugin_params else {
+            return;
+        };
+
+        for (param_info, param_value) in self.param_infos.iter().zip(&self.param_values) {
+            if let Some(param_id) = ClapId::from_raw(param_info.bind().id())
+                && let Some(value) = plugin_params.get_value(plugin_main_thread_handle, param_id)
+            {
+                param_value.set(value);
+            }
+        }
+    }
+
+    /// 插件侧的参数功能句柄，插件不支持参数功能时为空。
+    pub fn plugin_params(&self) -> Option<PluginParams> {
+        self.plugin_params
+    }
+
+    pub fn param_infos(&self) -> &[Gd<ClapParamInfo>] {
+        &self.param_infos
+    }
+
+    fn param_index(&self, param_id: ClapId) -> Option<usize> {
+        self.param_infos
+            .iter()
+            .position(|param_info| param_info.bind().id() == param_id.get())
+    }
+
+    /// 根据参数编号查找参数描述信息。
+    pub fn param_info(&self, param_id: ClapId) -> Option<&Gd<ClapParamInfo>> {
+        self.param_index(param_id)
+            .map(|index| &self.param_infos[index])
+    }
+
+    /// 根据Godot属性名查找参数描述信息。
+    pub fn param_info_by_property_name(
+        &self,
+        property_name: &StringName,
+    ) -> Option<&Gd<ClapParamInfo>> {
+        self.param_infos
+            .iter()
+            .find(|param_info| param_info.bind().property_name() == property_name)
+    }
+
+    /// 向插件查询参数的当前值，并更新缓存。
+    pub fn get_value(
+        &self,
+        plugin_main_thread_handle: &mut PluginMainThreadHandle,
+        param_id: ClapId,
+    ) -> Option<f64> {
+        let value = self
+            .plugin_params?
+            .get_value(plugin_main_thread_handle, param_id)?;
+        self.set_cached_value(param_id, value);
+        Some(value)
+    }
+
+    /// 让插件将参数值转换为用于显示的文本，例如`-12.0 dB`。
+    pub fn value_to_text(
+        &self,
+        plugin_main_thread_handle: &mut PluginMainThreadHandle,
+        param_id: ClapId,
+        value: f64,
+    ) -> Option<String> {
+        let mut buffer = [MaybeUninit::<u8>::uninit(); VALUE_TEXT_BUFFER_LENGTH];
+        let text = self
+            .plugin_params?
+            .value_to_text(plugin_main_thread_handle, param_id, value, &mut buffer)
+            .ok()?;
+        // 防止插件把结尾的`\0`也算进文本长度里。
+        let text = text.split(|byte| *byte == 0).next().unwrap_or_default();
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+
+    /// 让插件将用户输入的文本解析为参数值。
+    pub fn text_to_value(
+        &self,
+        plugin_main_thread_handle: &mut PluginMainThreadHandle,
+        param_id: ClapId,
+        text: &str,
+    ) -> Option<f64> {
+        let text = CString::new(text).ok()?;
+        self.plugin_params?
+            .text_to_value(plugin_main_thread_handle, param_id, &text)
+            .ok()
+    }
+
+    /// 读取缓存的参数值，不会访问插件。
+    pub fn cached_value(&self, param_id: ClapId) -> Option<f64> {
+        self.param_index(param_id)
+            .map(|index| self.param_values[index].get())
+    }
+
+    /// 更新缓存的参数值，不会通知插件。
+    pub fn set_cached_value(&self, param_id: ClapId, value: f64) {
+        if let Some(index) = self.param_index(param_id) {
+            self.param_values[index].set(value);
+        }
+    }
+}
+
+/// 参数值转换为文本时的缓冲区长度。
+const VALUE_TEXT_BUFFER_LENGTH: usize = 256;
+
+/// 为参数生成不重复的Godot属性名。
+///
+/// `:`在Godot的属性路径中是分隔符，所以需要被替换掉；重名的参数会在名称后面追加参数编号。
+fn unique_property_name(
+    param_info: &ParamInfo,
+    property_names: &mut HashSet<String>,
+) -> StringName {
+    let module = String::from_utf8_lossy(param_info.module);
+    let name = String::from_utf8_lossy(param_info.name);
+    let property_name = if module.is_empty() {
+        format!("params/{name}")
+    } else {
+        format!("params/{module}/{name}")
+    }
+    .replace(':', "_");
+
+    let property_name = if property_names.contains(&property_name) {
+        format!("{property_name}_{}", param_info.id.get())
+    } else {
+        property_name
+    };
+    property_names.insert(property_name.clone());
+    StringName::from(property_name.as_str())
+}
@@ -0,0 +1,43 @@
+use crate::host::ClapFileError;
+use clack_host::bundle::PluginBundle;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, PoisonError, Weak},
+};
+
+/// 已加载的clap文件，以规范化后的路径为键。
+///
+/// 只保存弱引用，所以不会阻止clap文件被卸载：最后一个持有它的主机销毁后，clap文件就会被卸载，下次使用时再重新加载。
+static PLUGIN_BUNDLES: LazyLock<Mutex<HashMap<PathBuf, Weak<PluginBundle>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 加载clap文件，同一个clap文件在被使用期间只会被加载一次。
+///
+/// 不同写法的路径（相对路径、符号链接等）只要指向同一个文件，就会共享同一份加载结果。
+pub fn load_plugin_bundle(path: &Path) -> Result<Arc<PluginBundle>, ClapFileError> {
+    let canonical_path = path
+        .canonicalize()
+        .map_err(|io_error| ClapFileError::InvalidPath {
+            path: path.to_path_buf(),
+            io_error,
+        })?;
+
+    let mut plugin_bundles = PLUGIN_BUNDLES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(plugin_bundle) = plugin_bundles.get(&canonical_path).and_then(Weak::upgrade) {
+        return Ok(plugin_bundle);
+    }
+
+    let plugin_bundle = Arc::new(unsafe { PluginBundle::load(&canonical_path) }.map_err(
+        |plugin_bundle_error| ClapFileError::PluginBundleError {
+            path: path.to_path_buf(),
+            plugin_bundle_error,
+        },
+    )?);
+    // 顺便清理掉已经被卸载的clap文件。
+    plugin_bundles.retain(|_, plugin_bundle| plugin_bundle.strong_count() > 0);
+    plugin_bundles.insert(canonical_path, Arc::downgrade(&plugin_bundle));
+    Ok(plugin_bundle)
+}
@@ -1,10 +1,33 @@
-use clack_extensions::gui::GuiSize;
+use clack_extensions::{
+    gui::GuiSize,
+    params::{ParamClearFlags, ParamRescanFlags},
+};
+use clack_host::utils::ClapId;
 
 /// 插件消息
 #[derive(Debug)]
 pub enum PluginMessage {
     RequestCallback,
+    /// 插件请求主机重启插件，即停用之后重新激活。
+    RequestRestart,
     Gui(PluginGuiMessage),
+    Params(PluginParamsMessage),
+    /// 插件的延迟发生了变化。
+    LatencyChanged,
+    /// 插件的音符端口发生了变化。
+    NotePortsRescanned,
+}
+impl PluginMessage {
+    /// 是否需要交给主机自行处理，而不是交给[`插件消息处理器`](crate::host::message_processor::MessageProcessor)处理。
+    pub fn is_for_host(&self) -> bool {
+        match self {
+            PluginMessage::RequestCallback | PluginMessage::Gui(..) => false,
+            PluginMessage::RequestRestart
+            | PluginMessage::Params(..)
+            | PluginMessage::LatencyChanged
+            | PluginMessage::NotePortsRescanned => true,
+        }
+    }
 }
 
 /// 插件GUI消息
@@ -15,3 +38,11 @@ pub enum PluginGuiMessage {
     RequestShow,
     RequestHide,
 }
+
+/// 插件参数消息
+#[derive(Debug)]
+pub enum PluginParamsMessage {
+    Rescan(ParamRescanFlags),
+    Clear(ClapId, ParamClearFlags),
+    RequestFlush,
+}
@@ -1,11 +1,22 @@
+mod audio_effect_clap;
+mod audio_effect_clap_instance;
+mod clap_audio_recorder;
+mod clap_audio_stream;
+mod clap_audio_stream_playback;
 mod clap_input_audio_channel_access;
 mod clap_input_audio_port_access;
 mod clap_output_audio_channel_access;
 mod clap_output_audio_port_access;
+mod clap_param_info;
+mod clap_plugin_catalog;
+mod clap_plugin_descriptor;
 mod clap_plugin_instance;
 mod clap_transport_event_access;
+mod clap_ump;
 mod host;
 mod midi;
+mod ring_buffer;
+mod wav;
 mod weak_ref;
 
 use godot::init::{ExtensionLibrary, gdextension};
@@ -14,4 +25,4 @@ struct GodinalClapLoader;
 #[gdextension]
 unsafe impl ExtensionLibrary for GodinalClapLoader {}
 
-// todo: 当前若插件GUI上有较多内容，则触发GUI刷新时会导致Godot线程阻塞。尝试将其改为非阻塞式。
\ No newline at end of file
+// todo: 当前若插件GUI上有较多内容，则触发GUI刷新时会导致Godot线程阻塞。尝试将其改为非阻塞式。
@@ -1,83 +1,469 @@
-use clack_host::{
-    events::{
-        Event, Pckn, UnknownEvent,
-        event_types::{MidiSysExEvent, NoteOffEvent, NoteOnEvent, ParamValueEvent},
-        spaces::CoreEventSpace,
+use crate::host::{EventTime, OutputEvent, ScheduledEvent};
+use clack_extensions::note_ports::NoteDialect;
+use clack_host::events::{
+    Event, Match, Pckn, UnknownEvent,
+    event_types::{
+        Midi2Event, MidiEvent, NoteExpressionEvent, NoteExpressionType, NoteOffEvent, NoteOnEvent,
     },
-    utils::{ClapId, Cookie},
+    spaces::CoreEventSpace,
 };
 use godot::{
     classes::InputEventMidi,
     global::{MidiMessage, godot_warn},
-    obj::{Gd, NewGd},
+    obj::{EngineEnum, Gd, NewGd},
 };
+use std::{error::Error, fmt::Display};
 
-fn to_unknown<E: Event>(event: E) -> Box<UnknownEvent> {
+pub fn to_unknown<E: Event>(event: E) -> Box<UnknownEvent> {
     let event = Box::new(event);
     let event_prt = event.as_unknown() as *const UnknownEvent as *mut UnknownEvent;
     let _ = Box::into_raw(event);
     unsafe { Box::from_raw(event_prt) }
 }
 
-fn assert_default_midi(event: Box<UnknownEvent>) -> Gd<InputEventMidi> {
+/// 将Godot端传入的整数转换为音符匹配规则[`Pckn`]，任一项为负数时表示匹配所有。
+pub fn pckn_from_godot(port_index: i32, channel: i32, key: i32, note_id: i32) -> Pckn {
+    fn to_match<T: TryFrom<i32>>(value: i32) -> Match<T> {
+        T::try_from(value).map_or(Match::All, Match::Specific)
+    }
+    Pckn::new(
+        to_match::<u16>(port_index),
+        to_match::<u16>(channel),
+        to_match::<u16>(key),
+        to_match::<u32>(note_id),
+    )
+}
+
+fn assert_default_midi(event: &UnknownEvent) -> Gd<InputEventMidi> {
     godot_warn!("尚未实现转换 Clap Event: {:?}", event);
     InputEventMidi::new_gd()
 }
 
-// todo: 完善下述映射。
+/// 将[`InputEventMidi`]转换为Clap事件时会出的错。
+#[derive(Debug)]
+pub enum MidiConversionError {
+    /// [`MidiMessage::NONE`]不对应任何midi消息。
+    NoMessage,
+    /// Godot新增了本插件尚不认识的消息类型。
+    UnsupportedMessage(MidiMessage),
+    /// 某一项的值超出了midi协议允许的范围。
+    ValueOutOfRange {
+        name: &'static str,
+        value: i32,
+        max: i32,
+    },
+    /// [`InputEventMidi`]无法携带系统独占消息的数据，需要改用原始字节发送。
+    SysExWithoutPayload,
+    /// 原始字节中出现了无法识别的字节，附带其下标。
+    InvalidByte { index: usize, byte: u8 },
+    /// 原始字节在一条消息的中途结束了。
+    IncompleteMessage,
+    /// 系统独占消息除去开头和结尾之外没有任何数据。
+    EmptySysEx,
+    /// 插件没有该下标的输入音符端口。
+    NoNotePort(u16),
+    /// 插件的输入音符端口不支持发送该消息所需的音符方言。
+    UnsupportedDialect {
+        port_index: u16,
+        dialect: &'static str,
+    },
+}
+impl Error for MidiConversionError {}
+impl Display for MidiConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiConversionError::NoMessage => write!(f, "midi事件没有设置消息类型"),
+            MidiConversionError::UnsupportedMessage(message) => {
+                write!(f, "不支持的midi消息类型：{message:?}")
+            }
+            MidiConversionError::ValueOutOfRange { name, value, max } => {
+                write!(f, "midi事件的{name}为{value}，超出了0到{max}的范围")
+            }
+            MidiConversionError::SysExWithoutPayload => write!(
+                f,
+                "InputEventMidi无法携带系统独占消息的数据，请改用send_sys_ex或send_midi_bytes发送"
+            ),
+            MidiConversionError::InvalidByte { index, byte } => {
+                write!(f, "midi数据的第{index}个字节{byte:#04X}无法识别")
+            }
+            MidiConversionError::IncompleteMessage => write!(f, "midi数据在消息的中途结束了"),
+            MidiConversionError::EmptySysEx => write!(f, "系统独占消息没有任何数据"),
+            MidiConversionError::NoNotePort(port_index) => {
+                write!(f, "插件没有下标为{port_index}的输入音符端口")
+            }
+            MidiConversionError::UnsupportedDialect {
+                port_index,
+                dialect,
+            } => write!(f, "插件的输入音符端口{port_index}不支持{dialect}方言"),
+        }
+    }
+}
+
+/// 检查`value`是否在`[0, max]`之内。
+fn checked_value(name: &'static str, value: i32, max: i32) -> Result<i32, MidiConversionError> {
+    if (0..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(MidiConversionError::ValueOutOfRange { name, value, max })
+    }
+}
 
-pub fn midi_to_event(midi: Gd<InputEventMidi>) -> Box<UnknownEvent> {
+/// 检查`value`是否是合法的7位midi数据字节。
+fn data_byte(name: &'static str, value: i32) -> Result<u8, MidiConversionError> {
+    checked_value(name, value, 0x7F).map(|value| value as u8)
+}
+
+/// 将Godot的midi事件转换为Clap事件。
+///
+/// `note_dialect`为[`NoteDialect::Clap`]时，音符事件和力度感应（复音触后）会转换为对应的Clap核心事件；
+/// 否则，以及其余没有对应核心事件的消息，包括控制变化，会原样作为3字节的[`MidiEvent`]发送。
+/// [`InputEventMidi`]没有单独存放系统公共消息数据字节的属性：
+/// 歌曲位置指针沿用弯音的做法，以`pitch`存放14位的值；四分之一帧和歌曲选择则以`controller_value`存放数据字节。
+pub fn midi_to_event(
+    midi: Gd<InputEventMidi>,
+    note_dialect: NoteDialect,
+) -> Result<Box<UnknownEvent>, MidiConversionError> {
     let time = 0;
-    let velocity = midi.get_velocity() as f64 / 127.0;
-    let param_id = ClapId::from_raw(midi.get_controller_number() as u32);
-    let port_index = midi.get_device() as u16;
-    let pckn = Pckn::new(
-        port_index,
-        midi.get_channel() as u16,
-        midi.get_pitch() as u16,
-        midi.instance_id().to_i64() as u32,
-    );
-    let value = midi.get_controller_value() as f64 / 127.0;
-    let cookie = Cookie::empty();
-
-    match midi.get_message() {
-        MidiMessage::NONE => todo!(),
-        MidiMessage::NOTE_OFF => to_unknown(NoteOffEvent::new(time, pckn, velocity)),
-        MidiMessage::NOTE_ON => to_unknown(NoteOnEvent::new(time, pckn, velocity)),
-        MidiMessage::AFTERTOUCH => todo!(),
-        MidiMessage::CONTROL_CHANGE => to_unknown(ParamValueEvent::new(
+    let port_index = checked_value("device", midi.get_device(), u16::MAX as i32)? as u16;
+    let channel = checked_value("channel", midi.get_channel(), 0x0F)? as u8;
+    let message = midi.get_message();
+    // 通道消息的状态字节高4位即为消息类型，系统消息的状态字节即为消息类型本身。
+    let channel_status = (message.ord() << 4) as u8 | channel;
+    // midi 1.0没有音符编号，同一个音符的开、关和触后可能来自不同的事件对象，只能按端口、通道和音高匹配。
+    let note_pckn = || -> Result<Pckn, MidiConversionError> {
+        Ok(Pckn::new(
+            port_index,
+            channel as u16,
+            data_byte("pitch", midi.get_pitch())? as u16,
+            Match::All,
+        ))
+    };
+    let midi_event = |data: [u8; 3]| to_unknown(MidiEvent::new(time, port_index, data));
+    let is_clap_dialect = matches!(note_dialect, NoteDialect::Clap);
+
+    let event = match message {
+        MidiMessage::NONE => return Err(MidiConversionError::NoMessage),
+        MidiMessage::NOTE_OFF | MidiMessage::NOTE_ON if !is_clap_dialect => midi_event([
+            channel_status,
+            data_byte("pitch", midi.get_pitch())?,
+            data_byte("velocity", midi.get_velocity())?,
+        ]),
+        MidiMessage::AFTERTOUCH if !is_clap_dialect => midi_event([
+            channel_status,
+            data_byte("pitch", midi.get_pitch())?,
+            data_byte("pressure", midi.get_pressure())?,
+        ]),
+        MidiMessage::NOTE_OFF => {
+            let velocity = data_byte("velocity", midi.get_velocity())? as f64 / 127.0;
+            to_unknown(NoteOffEvent::new(time, note_pckn()?, velocity))
+        }
+        MidiMessage::NOTE_ON => {
+            let velocity = data_byte("velocity", midi.get_velocity())? as f64 / 127.0;
+            // 按照midi的惯例，力度为0的音符开等同于音符关。
+            if velocity == 0.0 {
+                to_unknown(NoteOffEvent::new(time, note_pckn()?, velocity))
+            } else {
+                to_unknown(NoteOnEvent::new(time, note_pckn()?, velocity))
+            }
+        }
+        MidiMessage::AFTERTOUCH => {
+            let pressure = data_byte("pressure", midi.get_pressure())? as f64 / 127.0;
+            to_unknown(NoteExpressionEvent::new(
+                time,
+                note_pckn()?,
+                NoteExpressionType::Pressure,
+                pressure,
+            ))
+        }
+        MidiMessage::CONTROL_CHANGE => midi_event([
+            channel_status,
+            data_byte("controller_number", midi.get_controller_number())?,
+            data_byte("controller_value", midi.get_controller_value())?,
+        ]),
+        MidiMessage::PROGRAM_CHANGE => midi_event([
+            channel_status,
+            data_byte("instrument", midi.get_instrument())?,
+            0,
+        ]),
+        MidiMessage::CHANNEL_PRESSURE => midi_event([
+            channel_status,
+            data_byte("pressure", midi.get_pressure())?,
+            0,
+        ]),
+        MidiMessage::PITCH_BEND => {
+            let value = checked_value("pitch", midi.get_pitch(), 0x3FFF)?;
+            midi_event([channel_status, (value & 0x7F) as u8, (value >> 7) as u8])
+        }
+        MidiMessage::SYSTEM_EXCLUSIVE => return Err(MidiConversionError::SysExWithoutPayload),
+        MidiMessage::QUARTER_FRAME | MidiMessage::SONG_SELECT => midi_event([
+            message.ord() as u8,
+            data_byte("controller_value", midi.get_controller_value())?,
+            0,
+        ]),
+        MidiMessage::SONG_POSITION_POINTER => {
+            let value = checked_value("pitch", midi.get_pitch(), 0x3FFF)?;
+            midi_event([
+                message.ord() as u8,
+                (value & 0x7F) as u8,
+                (value >> 7) as u8,
+            ])
+        }
+        MidiMessage::TUNE_REQUEST
+        | MidiMessage::TIMING_CLOCK
+        | MidiMessage::START
+        | MidiMessage::CONTINUE
+        | MidiMessage::STOP
+        | MidiMessage::ACTIVE_SENSING
+        | MidiMessage::SYSTEM_RESET => midi_event([message.ord() as u8, 0, 0]),
+        _ => return Err(MidiConversionError::UnsupportedMessage(message)),
+    };
+    Ok(event)
+}
+
+/// 以`status`为状态字节的midi 1.0消息的总字节数，不是状态字节或是长度不定的系统独占消息时返回空。
+fn midi_message_length(status: u8) -> Option<usize> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF | 0xF2 => Some(3),
+        0xC0..=0xDF | 0xF1 | 0xF3 => Some(2),
+        0xF6 | 0xF8 | 0xFA..=0xFC | 0xFE | 0xFF => Some(1),
+        _ => None,
+    }
+}
+
+/// 将原始的midi 1.0字节流拆分为一条条消息，并转换为在`time`生效的事件。
+///
+/// 支持连续的多条消息、省略状态字节的运行状态，以及穿插在消息之间的实时消息。
+/// 系统独占消息需要以`0xF0`开头、以`0xF7`结尾，中间不能穿插其他消息。
+pub fn midi_bytes_to_events(
+    port_index: u16,
+    bytes: &[u8],
+    time: EventTime,
+) -> Result<Vec<ScheduledEvent>, MidiConversionError> {
+    let mut events = Vec::new();
+    let mut running_status = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if byte == 0xF0 {
+            let end_index = sys_ex_end_index(bytes, index)?;
+            events.push(ScheduledEvent::sys_ex(
+                time,
+                port_index,
+                bytes[index..=end_index].into(),
+            ));
+            running_status = None;
+            index = end_index + 1;
+            continue;
+        }
+
+        let (status, data_start_index) = if byte & 0x80 == 0 {
+            let status = running_status.ok_or(MidiConversionError::InvalidByte { index, byte })?;
+            (status, index)
+        } else {
+            (byte, index + 1)
+        };
+        let length =
+            midi_message_length(status).ok_or(MidiConversionError::InvalidByte { index, byte })?;
+        let data_end_index = data_start_index + length - 1;
+        let data_bytes = bytes
+            .get(data_start_index..data_end_index)
+            .ok_or(MidiConversionError::IncompleteMessage)?;
+        if let Some(offset) = data_bytes
+            .iter()
+            .position(|data_byte| data_byte & 0x80 != 0)
+        {
+            let index = data_start_index + offset;
+            return Err(MidiConversionError::InvalidByte {
+                index,
+                byte: bytes[index],
+            });
+        }
+
+        let mut data = [status, 0, 0];
+        data[1..length].copy_from_slice(data_bytes);
+        events.push(ScheduledEvent::new(
             time,
-            param_id.unwrap(),
-            pckn,
-            value,
-            cookie,
+            to_unknown(MidiEvent::new(0, port_index, data)),
+        ));
+        match status {
+            // 只有通道消息会成为运行状态，系统公共消息会取消运行状态，实时消息则不影响运行状态。
+            0x80..=0xEF => running_status = Some(status),
+            0xF0..=0xF7 => running_status = None,
+            _ => {}
+        }
+        index = data_end_index;
+    }
+    Ok(events)
+}
+
+/// 从`start_index`处的`0xF0`开始，找到系统独占消息结尾的`0xF7`的下标。
+fn sys_ex_end_index(bytes: &[u8], start_index: usize) -> Result<usize, MidiConversionError> {
+    for (index, &byte) in bytes.iter().enumerate().skip(start_index + 1) {
+        match byte {
+            0xF7 => return Ok(index),
+            0x80.. => return Err(MidiConversionError::InvalidByte { index, byte }),
+            _ => {}
+        }
+    }
+    Err(MidiConversionError::IncompleteMessage)
+}
+
+/// 将一条系统独占消息转换为事件，缺少开头的`0xF0`或结尾的`0xF7`时会自动补上。
+///
+/// 除去开头和结尾之外没有任何数据的消息会被拒绝。
+pub fn sys_ex_to_event(
+    port_index: u16,
+    sys_ex: &[u8],
+    time: EventTime,
+) -> Result<ScheduledEvent, MidiConversionError> {
+    if matches!(sys_ex, [] | [0xF0] | [0xF7] | [0xF0, 0xF7]) {
+        return Err(MidiConversionError::EmptySysEx);
+    }
+    let mut bytes = Vec::with_capacity(sys_ex.len() + 2);
+    if sys_ex.first() != Some(&0xF0) {
+        bytes.push(0xF0);
+    }
+    bytes.extend_from_slice(sys_ex);
+    if sys_ex.last() != Some(&0xF7) {
+        bytes.push(0xF7);
+    }
+    let end_index = sys_ex_end_index(&bytes, 0)?;
+    if end_index != bytes.len() - 1 {
+        return Err(MidiConversionError::InvalidByte {
+            index: end_index,
+            byte: 0xF7,
+        });
+    }
+    Ok(ScheduledEvent::sys_ex(time, port_index, bytes.into()))
+}
+
+/// 取出原始midi事件或系统独占事件的端口和字节，原始midi事件多余的填充字节会被去掉。
+///
+/// 返回：不是这两种事件时为空。
+pub fn event_to_midi_bytes(output_event: &OutputEvent) -> Option<(u16, Vec<u8>)> {
+    match output_event.event().as_core_event()? {
+        CoreEventSpace::Midi(midi_event) => {
+            let data = midi_event.data();
+            let length = midi_message_length(data[0]).unwrap_or(data.len());
+            Some((midi_event.port_index(), data[..length].to_vec()))
+        }
+        CoreEventSpace::MidiSysEx(midi_sys_ex_event) => Some((
+            midi_sys_ex_event.port_index(),
+            output_event.sys_ex.as_deref()?.to_vec(),
         )),
-        MidiMessage::PROGRAM_CHANGE => todo!(),
-        MidiMessage::CHANNEL_PRESSURE => todo!(),
-        MidiMessage::PITCH_BEND => todo!(),
-        MidiMessage::SYSTEM_EXCLUSIVE => to_unknown(MidiSysExEvent::new(time, port_index, &[])),
-        MidiMessage::QUARTER_FRAME => todo!(),
-        MidiMessage::SONG_POSITION_POINTER => todo!(),
-        MidiMessage::SONG_SELECT => todo!(),
-        MidiMessage::TUNE_REQUEST => todo!(),
-        MidiMessage::TIMING_CLOCK => todo!(),
-        MidiMessage::START => todo!(),
-        MidiMessage::CONTINUE => todo!(),
-        MidiMessage::STOP => todo!(),
-        MidiMessage::ACTIVE_SENSING => todo!(),
-        MidiMessage::SYSTEM_RESET => todo!(),
-        _ => unreachable!("未处理的Midi事件：{:?}", midi.get_message()),
-    }
-}
-
-pub fn event_to_midi(event: Box<UnknownEvent>) -> Gd<InputEventMidi> {
+        _ => None,
+    }
+}
+
+/// 以`first_word`开头的UMP数据包的字数，由数据包开头4位的消息类型决定。
+fn ump_packet_words_count(first_word: u32) -> usize {
+    match first_word >> 28 {
+        0x0..=0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8..=0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+/// 将UMP（通用midi数据包）字流拆分为一个个数据包，并转换为在`time`生效的事件。
+///
+/// 每个[`Midi2Event`]正好容纳一个最长128位的数据包，较短的数据包不足的部分补0。
+pub fn ump_to_events(
+    port_index: u16,
+    words: &[u32],
+    time: EventTime,
+) -> Result<Vec<ScheduledEvent>, MidiConversionError> {
+    let mut events = Vec::new();
+    let mut index = 0;
+    while let Some(&first_word) = words.get(index) {
+        let words_count = ump_packet_words_count(first_word);
+        let packet = words
+            .get(index..index + words_count)
+            .ok_or(MidiConversionError::IncompleteMessage)?;
+        let mut data = [0; 4];
+        data[..words_count].copy_from_slice(packet);
+        events.push(ScheduledEvent::new(
+            time,
+            to_unknown(Midi2Event::new(0, port_index, data)),
+        ));
+        index += words_count;
+    }
+    Ok(events)
+}
+
+/// 取出midi 2.0事件的端口和UMP数据包，补齐用的0会被去掉。
+///
+/// 返回：不是midi 2.0事件时为空。
+pub fn event_to_ump(event: &UnknownEvent) -> Option<(u16, Vec<u32>)> {
+    let CoreEventSpace::Midi2(midi2_event) = event.as_core_event()? else {
+        return None;
+    };
+    let data = midi2_event.data();
+    let words_count = ump_packet_words_count(data[0]);
+    Some((midi2_event.port_index(), data[..words_count].to_vec()))
+}
+
+/// 将一条原始的midi 1.0消息转换为Godot的midi事件，是[`midi_to_event`]中对原始消息的逆向转换。
+fn midi_from_bytes(port_index: u16, data: [u8; 3]) -> Gd<InputEventMidi> {
+    let mut midi = InputEventMidi::new_gd();
+    midi.set_device(port_index as i32);
+    let [status, data1, data2] = data;
+    let fourteen_bits = data1 as i32 | (data2 as i32) << 7;
+    if status >= 0xF0 {
+        let Some(message) = MidiMessage::try_from_ord(status as i32) else {
+            godot_warn!("无法识别的midi系统消息：{data:02X?}");
+            return midi;
+        };
+        midi.set_message(message);
+        match message {
+            MidiMessage::QUARTER_FRAME | MidiMessage::SONG_SELECT => {
+                midi.set_controller_value(data1 as i32);
+            }
+            MidiMessage::SONG_POSITION_POINTER => midi.set_pitch(fourteen_bits),
+            _ => {}
+        }
+        return midi;
+    }
+
+    let Some(message) = MidiMessage::try_from_ord((status >> 4) as i32) else {
+        godot_warn!("无法识别的midi消息：{data:02X?}");
+        return midi;
+    };
+    midi.set_message(message);
+    midi.set_channel((status & 0x0F) as i32);
+    match message {
+        MidiMessage::NOTE_OFF | MidiMessage::NOTE_ON => {
+            midi.set_pitch(data1 as i32);
+            midi.set_velocity(data2 as i32);
+        }
+        MidiMessage::AFTERTOUCH => {
+            midi.set_pitch(data1 as i32);
+            midi.set_pressure(data2 as i32);
+        }
+        MidiMessage::CONTROL_CHANGE => {
+            midi.set_controller_number(data1 as i32);
+            midi.set_controller_value(data2 as i32);
+        }
+        MidiMessage::PROGRAM_CHANGE => midi.set_instrument(data1 as i32),
+        MidiMessage::CHANNEL_PRESSURE => midi.set_pressure(data1 as i32),
+        MidiMessage::PITCH_BEND => midi.set_pitch(fourteen_bits),
+        _ => {}
+    }
+    midi
+}
+
+/// 将插件输出的事件转换为Godot的midi事件。
+///
+/// 返回：系统独占事件为空，[`InputEventMidi`]无法携带它们的数据。
+pub fn event_to_midi(event: &UnknownEvent) -> Option<Gd<InputEventMidi>> {
     let Some(core_event) = event.as_core_event() else {
-        return assert_default_midi(event);
+        return Some(assert_default_midi(event));
     };
 
     let mut midi = InputEventMidi::new_gd();
-    match core_event {
+    let midi = match core_event {
         CoreEventSpace::NoteOn(note_on_event) => {
+            midi.set_message(MidiMessage::NOTE_ON);
             midi.set_velocity((note_on_event.velocity() * 127.0) as i32);
             midi.set_device(note_on_event.port_index().to_raw() as i32);
             midi.set_channel(note_on_event.channel().to_raw() as i32);
@@ -85,6 +471,7 @@ pub fn event_to_midi(event: Box<UnknownEvent>) -> Gd<InputEventMidi> {
             midi
         }
         CoreEventSpace::NoteOff(note_off_event) => {
+            midi.set_message(MidiMessage::NOTE_OFF);
             midi.set_velocity((note_off_event.velocity() * 127.0) as i32);
             midi.set_device(note_off_event.port_index().to_raw() as i32);
             midi.set_channel(note_off_event.channel().to_raw() as i32);
@@ -94,27 +481,175 @@ pub fn event_to_midi(event: Box<UnknownEvent>) -> Gd<InputEventMidi> {
         CoreEventSpace::NoteChoke(note_choke_event) => assert_default_midi(event),
         CoreEventSpace::NoteEnd(note_end_event) => assert_default_midi(event),
         CoreEventSpace::NoteExpression(note_expression_event) => assert_default_midi(event),
-        CoreEventSpace::ParamValue(param_value_event) => {
-            midi.set_controller_number(
-                param_value_event
-                    .param_id()
-                    .map_or(0, |clap_id| clap_id.get() as i32),
-            );
-            midi.set_device(param_value_event.port_index().to_raw() as i32);
-            midi.set_channel(param_value_event.channel().to_raw() as i32);
-            midi.set_pitch(param_value_event.key().to_raw() as i32);
-            midi.set_controller_value((param_value_event.value() * 127.0) as i32);
-            midi
-        }
+        // 参数事件由`ClapPluginInstance`的`param_changed`等信号单独处理，不会被转换为midi。
+        CoreEventSpace::ParamValue(param_value_event) => assert_default_midi(event),
         CoreEventSpace::ParamMod(param_mod_event) => assert_default_midi(event),
         CoreEventSpace::ParamGestureBegin(param_gesture_begin_event) => assert_default_midi(event),
         CoreEventSpace::ParamGestureEnd(param_gesture_end_event) => assert_default_midi(event),
         CoreEventSpace::Transport(transport_event) => assert_default_midi(event),
-        CoreEventSpace::Midi(midi_event) => assert_default_midi(event),
+        CoreEventSpace::Midi(midi_event) => {
+            midi_from_bytes(midi_event.port_index(), midi_event.data())
+        }
+        // midi 2.0事件由`ClapPluginInstance`的`ump_received`信号单独处理，不会被转换为midi。
         CoreEventSpace::Midi2(midi2_event) => assert_default_midi(event),
-        CoreEventSpace::MidiSysEx(midi_sys_ex_event) => {
-            midi.set_device(midi_sys_ex_event.port_index() as i32);
-            midi
+        // 系统独占事件只经`ClapPluginInstance`的`midi_bytes_received`信号发出。
+        CoreEventSpace::MidiSysEx(_) => return None,
+    };
+    Some(midi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn midi_data(event: &ScheduledEvent) -> [u8; 3] {
+        match event.event().as_core_event() {
+            Some(CoreEventSpace::Midi(midi_event)) => midi_event.data(),
+            _ => panic!("不是原始midi事件"),
+        }
+    }
+
+    #[test]
+    fn midi_bytes_running_status() {
+        let events = midi_bytes_to_events(
+            0,
+            &[0x90, 0x3C, 0x7F, 0x3E, 0x7F, 0xF8, 0x3C, 0x00],
+            EventTime::FrameOffset(0),
+        )
+        .unwrap();
+        let data: Vec<_> = events.iter().map(midi_data).collect();
+        // 实时消息不会打断运行状态。
+        assert_eq!(
+            data,
+            [
+                [0x90, 0x3C, 0x7F],
+                [0x90, 0x3E, 0x7F],
+                [0xF8, 0, 0],
+                [0x90, 0x3C, 0x00]
+            ]
+        );
+    }
+
+    #[test]
+    fn midi_bytes_sys_ex_between_messages() {
+        let events = midi_bytes_to_events(
+            2,
+            &[0xF0, 0x7E, 0x01, 0xF7, 0xC0, 0x05],
+            EventTime::FrameOffset(0),
+        )
+        .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].payload(), Some(&[0xF0, 0x7E, 0x01, 0xF7][..]));
+        assert_eq!(midi_data(&events[1]), [0xC0, 0x05, 0]);
+    }
+
+    #[test]
+    fn midi_bytes_truncated_message() {
+        assert!(matches!(
+            midi_bytes_to_events(0, &[0x90, 0x3C], EventTime::FrameOffset(0)),
+            Err(MidiConversionError::IncompleteMessage)
+        ));
+        assert!(matches!(
+            midi_bytes_to_events(0, &[0xF0, 0x7E, 0x01], EventTime::FrameOffset(0)),
+            Err(MidiConversionError::IncompleteMessage)
+        ));
+    }
+
+    #[test]
+    fn midi_bytes_stray_data_byte() {
+        assert!(matches!(
+            midi_bytes_to_events(0, &[0x3C, 0x7F], EventTime::FrameOffset(0)),
+            Err(MidiConversionError::InvalidByte {
+                index: 0,
+                byte: 0x3C
+            })
+        ));
+        // 系统公共消息会取消运行状态。
+        assert!(matches!(
+            midi_bytes_to_events(
+                0,
+                &[0x90, 0x3C, 0x7F, 0xF3, 0x01, 0x3C],
+                EventTime::FrameOffset(0)
+            ),
+            Err(MidiConversionError::InvalidByte {
+                index: 5,
+                byte: 0x3C
+            })
+        ));
+        // 消息中途出现状态字节。
+        assert!(matches!(
+            midi_bytes_to_events(0, &[0x90, 0x3C, 0x80, 0x00], EventTime::FrameOffset(0)),
+            Err(MidiConversionError::InvalidByte {
+                index: 2,
+                byte: 0x80
+            })
+        ));
+    }
+
+    #[test]
+    fn sys_ex_framing() {
+        let event = sys_ex_to_event(0, &[0x7E, 0x01], EventTime::FrameOffset(0)).unwrap();
+        assert_eq!(event.payload(), Some(&[0xF0, 0x7E, 0x01, 0xF7][..]));
+        let event = sys_ex_to_event(0, &[0xF0, 0x7E, 0xF7], EventTime::FrameOffset(0)).unwrap();
+        assert_eq!(event.payload(), Some(&[0xF0, 0x7E, 0xF7][..]));
+        for empty in [&[][..], &[0xF0], &[0xF7], &[0xF0, 0xF7]] {
+            assert!(matches!(
+                sys_ex_to_event(0, empty, EventTime::FrameOffset(0)),
+                Err(MidiConversionError::EmptySysEx)
+            ));
+        }
+    }
+
+    #[test]
+    fn ump_packet_words_count_by_message_type() {
+        for (message_type, words_count) in [
+            (0x0, 1),
+            (0x1, 1),
+            (0x2, 1),
+            (0x3, 2),
+            (0x4, 2),
+            (0x5, 4),
+            (0x6, 1),
+            (0x7, 1),
+            (0x8, 2),
+            (0xA, 2),
+            (0xB, 3),
+            (0xC, 3),
+            (0xD, 4),
+            (0xF, 4),
+        ] {
+            assert_eq!(
+                ump_packet_words_count(message_type << 28),
+                words_count,
+                "消息类型{message_type:#X}"
+            );
         }
     }
+
+    #[test]
+    fn ump_to_events_splits_packets() {
+        let note_on = [0x4090_3C00, 0xFFFF_0000];
+        let midi1_note_off = [0x2080_3C00];
+        let words = [&note_on[..], &midi1_note_off[..]].concat();
+        let events = ump_to_events(1, &words, EventTime::FrameOffset(0)).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(event_to_ump(events[0].event()), Some((1, note_on.to_vec())));
+        assert_eq!(
+            event_to_ump(events[1].event()),
+            Some((1, midi1_note_off.to_vec()))
+        );
+    }
+
+    #[test]
+    fn ump_to_events_rejects_truncated_packet() {
+        assert!(matches!(
+            ump_to_events(0, &[0x4090_3C00], EventTime::FrameOffset(0)),
+            Err(MidiConversionError::IncompleteMessage)
+        ));
+        assert!(
+            ump_to_events(0, &[], EventTime::FrameOffset(0))
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,326 @@
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// 创建一个单生产者单消费者的无锁环形缓冲，用于在Godot与音频线程之间传递数据。
+///
+/// 内存在创建时一次性分配好，之后的读写既不会分配内存，也不会加锁。
+/// - `capacity`: 至少能滞留的元素个数，会被向上取整为2的幂。
+pub fn ring_buffer<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let shared = Arc::new(Shared {
+        slots: (0..capacity.max(1).next_power_of_two())
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        read_index: AtomicUsize::new(0),
+        write_index: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+struct Shared<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// 消费者下一次读取的位置，只增不减并在溢出时回绕，对容量取余后才是下标。
+    read_index: AtomicUsize,
+    /// 生产者下一次写入的位置，只增不减并在溢出时回绕，对容量取余后才是下标。
+    ///
+    /// 容量是2的幂，整除`usize::MAX + 1`，所以下标回绕之后取余的结果依然连续。
+    write_index: AtomicUsize,
+}
+// SAFETY: 读写下标保证了同一个槽位在同一时间只会被生产者和消费者中的一方访问。
+unsafe impl<T: Send> Sync for Shared<T> {}
+impl<T> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index & (self.capacity() - 1)].get()
+    }
+}
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let read_index = *self.read_index.get_mut();
+        let write_index = *self.write_index.get_mut();
+        for offset in 0..write_index.wrapping_sub(read_index) {
+            let index = read_index.wrapping_add(offset);
+            // SAFETY: `[read_index, write_index)`之间的槽位都已被写入且尚未被读出。
+            unsafe { (*self.slot(index)).assume_init_drop() };
+        }
+    }
+}
+
+/// 环形缓冲的写入端。
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+impl<T> Producer<T> {
+    /// 还能写入的元素个数。
+    pub fn free_len(&self) -> usize {
+        let read_index = self.shared.read_index.load(Ordering::Acquire);
+        let write_index = self.shared.write_index.load(Ordering::Relaxed);
+        self.shared.capacity() - write_index.wrapping_sub(read_index)
+    }
+
+    /// 写入一个元素，缓冲已满时原样返回。
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.free_len() == 0 {
+            return Err(value);
+        }
+        let write_index = self.shared.write_index.load(Ordering::Relaxed);
+        // SAFETY: 该槽位不在`[read_index, write_index)`之间，消费者不会访问。
+        unsafe { (*self.shared.slot(write_index)).write(value) };
+        self.shared
+            .write_index
+            .store(write_index.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+impl<T: Copy> Producer<T> {
+    /// 尽可能多地写入`values`的开头部分。
+    ///
+    /// 返回：实际写入的元素个数。
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        let values = &values[..values.len().min(self.free_len())];
+        self.write(0, values);
+        self.publish(values.len());
+        values.len()
+    }
+
+    /// 将各段数据作为一个整体写入，消费者要么读到全部，要么一段都读不到。
+    ///
+    /// 返回：是否写入成功，空间不足时什么都不写。
+    pub fn push_slices(&mut self, parts: &[&[T]]) -> bool {
+        let total_len = parts.iter().map(|part| part.len()).sum::<usize>();
+        if total_len > self.free_len() {
+            return false;
+        }
+        // 先写好所有的槽位，最后一次性移动写入下标，消费者才不会读到写了一半的数据。
+        let mut offset = 0;
+        for part in parts {
+            self.write(offset, part);
+            offset += part.len();
+        }
+        self.publish(total_len);
+        true
+    }
+
+    /// 从写入下标之后`offset`个元素的位置开始写入数据，此时数据对消费者还不可见。
+    /// 调用者需要保证空间足够。
+    fn write(&mut self, offset: usize, values: &[T]) {
+        let write_index = self
+            .shared
+            .write_index
+            .load(Ordering::Relaxed)
+            .wrapping_add(offset);
+        for (value_offset, value) in values.iter().enumerate() {
+            // SAFETY: 调用者保证了空间足够，这些槽位都不在`[read_index, write_index)`之间。
+            unsafe { (*self.shared.slot(write_index.wrapping_add(value_offset))).write(*value) };
+        }
+    }
+
+    /// 让之前写入的`count`个元素对消费者可见。
+    fn publish(&mut self, count: usize) {
+        let write_index = self.shared.write_index.load(Ordering::Relaxed);
+        self.shared
+            .write_index
+            .store(write_index.wrapping_add(count), Ordering::Release);
+    }
+}
+
+/// 环形缓冲的读取端。
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+impl<T> Consumer<T> {
+    /// 可以读取的元素个数。
+    pub fn len(&self) -> usize {
+        let read_index = self.shared.read_index.load(Ordering::Relaxed);
+        let write_index = self.shared.write_index.load(Ordering::Acquire);
+        write_index.wrapping_sub(read_index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 读取一个元素，缓冲为空时返回空。
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let read_index = self.shared.read_index.load(Ordering::Relaxed);
+        // SAFETY: 该槽位在`[read_index, write_index)`之间，已被生产者写入，且生产者不会再访问。
+        let value = unsafe { (*self.shared.slot(read_index)).assume_init_read() };
+        self.shared
+            .read_index
+            .store(read_index.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// 逐个读取当前所有的元素。
+    pub fn pop_iter(&mut self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map_while(|_| self.pop())
+    }
+}
+impl<T: Copy> Consumer<T> {
+    /// 读取至多`values.len()`个元素到`values`的开头。
+    ///
+    /// 返回：实际读取的元素个数。
+    pub fn pop_slice(&mut self, values: &mut [T]) -> usize {
+        let values_count = values.len().min(self.len());
+        let read_index = self.shared.read_index.load(Ordering::Relaxed);
+        for (offset, value) in values[..values_count].iter_mut().enumerate() {
+            // SAFETY: 这些槽位都在`[read_index, write_index)`之间。
+            *value = unsafe { (*self.shared.slot(read_index.wrapping_add(offset))).assume_init() };
+        }
+        self.shared
+            .read_index
+            .store(read_index.wrapping_add(values_count), Ordering::Release);
+        values_count
+    }
+
+    /// 丢弃当前所有的元素。
+    pub fn clear(&mut self) {
+        let write_index = self.shared.write_index.load(Ordering::Acquire);
+        self.shared.read_index.store(write_index, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /// 把读写下标移到`usize::MAX`附近，以便测试下标溢出后的回绕。
+    fn set_indices<T>(producer: &Producer<T>, index: usize) {
+        producer.shared.read_index.store(index, Ordering::Relaxed);
+        producer.shared.write_index.store(index, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn capacity_is_rounded_up_to_power_of_two() {
+        let (producer, consumer) = ring_buffer::<u8>(3);
+        assert_eq!(producer.free_len(), 4);
+        assert_eq!(consumer.len(), 0);
+
+        let (producer, _) = ring_buffer::<u8>(0);
+        assert_eq!(producer.free_len(), 1);
+    }
+
+    #[test]
+    fn full_and_empty() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.pop(), None);
+
+        for value in 0..4 {
+            assert_eq!(producer.push(value), Ok(()));
+        }
+        assert_eq!(producer.free_len(), 0);
+        assert_eq!(producer.push(4), Err(4));
+        assert_eq!(producer.push_slice(&[5, 6]), 0);
+        assert_eq!(consumer.len(), 4);
+
+        assert_eq!(consumer.pop_iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert!(consumer.is_empty());
+        assert_eq!(producer.free_len(), 4);
+    }
+
+    #[test]
+    fn wraparound() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        let mut values = [0; 4];
+        for round in 0..10 {
+            assert_eq!(producer.push_slice(&[round, round + 1, round + 2]), 3);
+            assert_eq!(consumer.pop_slice(&mut values), 3);
+            assert_eq!(values[..3], [round, round + 1, round + 2]);
+        }
+    }
+
+    #[test]
+    fn wraparound_on_index_overflow() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        set_indices(&producer, usize::MAX - 1);
+        assert_eq!(producer.push_slice(&[1, 2, 3, 4, 5]), 4);
+        assert_eq!(producer.free_len(), 0);
+        assert_eq!(consumer.len(), 4);
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(producer.push(5), Ok(()));
+        assert_eq!(consumer.pop_iter().collect::<Vec<_>>(), [2, 3, 4, 5]);
+
+        set_indices(&producer, usize::MAX);
+        producer.push_slice(&[6, 7]);
+        consumer.clear();
+        assert!(consumer.is_empty());
+        assert_eq!(producer.free_len(), 4);
+    }
+
+    #[test]
+    fn push_slices_is_all_or_nothing() {
+        let (mut producer, mut consumer) = ring_buffer(4);
+        assert!(producer.push_slices(&[&[1, 2], &[3]]));
+        assert!(!producer.push_slices(&[&[4], &[5]]));
+        assert_eq!(consumer.len(), 3);
+        assert!(producer.push_slices(&[&[4], &[]]));
+        assert_eq!(consumer.pop_iter().collect::<Vec<_>>(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn push_slices_is_atomic_across_threads() {
+        const RECORDS_COUNT: u32 = 100_000;
+        let (mut producer, mut consumer) = ring_buffer::<u32>(64);
+        let producer_thread = std::thread::spawn(move || {
+            for record in 0..RECORDS_COUNT {
+                let body: Vec<_> = (0..record % 7).map(|offset| record + offset).collect();
+                while !producer.push_slices(&[&[body.len() as u32], &[record], &body]) {
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut length = [0];
+        let mut record = [0];
+        for expected_record in 0..RECORDS_COUNT {
+            while consumer.pop_slice(&mut length) == 0 {
+                std::thread::yield_now();
+            }
+            // 读到了长度，同一条记录的其余部分就必须已经可见。
+            let mut body = vec![0; length[0] as usize];
+            assert_eq!(consumer.pop_slice(&mut record), 1);
+            assert_eq!(consumer.pop_slice(&mut body), body.len());
+            assert_eq!(record[0], expected_record);
+            assert!(
+                body.iter()
+                    .enumerate()
+                    .all(|(offset, &value)| value == expected_record + offset as u32)
+            );
+        }
+        producer_thread.join().unwrap();
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn drops_remaining_items() {
+        let value = Rc::new(());
+        let (mut producer, mut consumer) = ring_buffer(4);
+        set_indices(&producer, usize::MAX - 1);
+        for _ in 0..4 {
+            assert!(producer.push(value.clone()).is_ok());
+        }
+        drop(consumer.pop());
+        assert_eq!(Rc::strong_count(&value), 4);
+
+        drop((producer, consumer));
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
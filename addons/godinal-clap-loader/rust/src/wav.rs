@@ -0,0 +1,233 @@
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// WAV文件头的长度，数据块紧随其后。
+const HEADER_LENGTH: u32 = 44;
+
+/// WAV文件中每个采样的格式。
+#[derive(Clone, Copy, Debug)]
+pub enum WavSampleFormat {
+    /// 16位整数。
+    Pcm16,
+    /// 24位整数。
+    Pcm24,
+    /// 32位浮点数。
+    Float32,
+}
+impl WavSampleFormat {
+    /// 由每个采样的位数得到采样格式，只支持16、24和32位。
+    pub fn from_bits_per_sample(bits_per_sample: i64) -> Option<Self> {
+        match bits_per_sample {
+            16 => Some(Self::Pcm16),
+            24 => Some(Self::Pcm24),
+            32 => Some(Self::Float32),
+            _ => None,
+        }
+    }
+
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            Self::Pcm16 => 2,
+            Self::Pcm24 => 3,
+            Self::Float32 => 4,
+        }
+    }
+
+    /// fmt块中的格式标签：1为整数PCM，3为IEEE浮点数。
+    fn format_tag(self) -> u16 {
+        match self {
+            Self::Pcm16 | Self::Pcm24 => 1,
+            Self::Float32 => 3,
+        }
+    }
+
+    /// 将一个采样以小端序编码到`bytes`的末尾，整数格式会先将采样限制在`[-1.0, 1.0]`之内。
+    fn encode(self, sample: f32, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Pcm16 => {
+                let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                bytes.extend_from_slice(&sample.to_le_bytes());
+            }
+            Self::Pcm24 => {
+                let sample = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+                bytes.extend_from_slice(&sample.to_le_bytes()[..3]);
+            }
+            Self::Float32 => bytes.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+}
+
+/// 将各个通道的音频交错编码，较短的通道不足的部分视为静音。
+pub fn encode_interleaved(sample_format: WavSampleFormat, channels: &[&[f32]]) -> Vec<u8> {
+    let frames_count = channels
+        .iter()
+        .map(|channel| channel.len())
+        .max()
+        .unwrap_or(0);
+    let mut bytes = Vec::with_capacity(
+        frames_count * channels.len() * sample_format.bytes_per_sample() as usize,
+    );
+    for frame_index in 0..frames_count {
+        for channel in channels {
+            let sample = channel.get(frame_index).copied().unwrap_or(0.0);
+            sample_format.encode(sample, &mut bytes);
+        }
+    }
+    bytes
+}
+
+/// 逐块写入的WAV文件编码器。
+///
+/// 文件头中的长度在写入结束前是未知的，写完后需要调用[`Self::finish`]补全，否则文件会被视为空白。
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    sample_format: WavSampleFormat,
+    channels_count: u16,
+    /// 已写入的音频数据的字节数。
+    data_length: u64,
+}
+impl<W: Write + Seek> WavWriter<W> {
+    /// 构造，并写入长度暂时为0的文件头。
+    pub fn new(
+        mut writer: W,
+        sample_format: WavSampleFormat,
+        channels_count: u16,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        let block_align = channels_count * sample_format.bytes_per_sample();
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_LENGTH - 8).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&sample_format.format_tag().to_le_bytes())?;
+        writer.write_all(&channels_count.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(sample_format.bytes_per_sample() * 8).to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            sample_format,
+            channels_count,
+            data_length: 0,
+        })
+    }
+
+    /// 追加一段音频，`channels`中缺少的通道视为静音，多出的通道会被忽略。
+    pub fn write_channels(&mut self, channels: &[&[f32]]) -> io::Result<()> {
+        let mut channels = channels.to_vec();
+        channels.resize(self.channels_count as usize, &[]);
+        let bytes = encode_interleaved(self.sample_format, &channels);
+        self.writer.write_all(&bytes)?;
+        self.data_length += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// 补全文件头中的长度。数据块的长度为奇数时，按RIFF的要求在末尾补一个字节。
+    ///
+    /// WAV文件的长度上限为4GiB，超出的部分仍会被写入，但文件头中的长度会停留在上限。
+    pub fn finish(mut self) -> io::Result<W> {
+        let pad_length = (self.data_length % 2) as u32;
+        if pad_length != 0 {
+            self.writer.write_all(&[0])?;
+        }
+        let data_length = u32::try_from(self.data_length)
+            .unwrap_or(u32::MAX)
+            .min(u32::MAX - HEADER_LENGTH - 1);
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_LENGTH - 8 + data_length + pad_length).to_le_bytes())?;
+        self.writer
+            .seek(SeekFrom::Start(HEADER_LENGTH as u64 - 4))?;
+        self.writer.write_all(&data_length.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u16_at(bytes: &[u8], index: usize) -> u16 {
+        u16::from_le_bytes(bytes[index..index + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], index: usize) -> u32 {
+        u32::from_le_bytes(bytes[index..index + 4].try_into().unwrap())
+    }
+
+    /// 解析写出的文件，返回数据块中交错排列的采样。
+    fn read_samples(bytes: &[u8], sample_format: WavSampleFormat, channels_count: u16) -> Vec<f32> {
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(bytes, 20), sample_format.format_tag());
+        assert_eq!(u16_at(bytes, 22), channels_count);
+        assert_eq!(u32_at(bytes, 24), 48000);
+        let block_align = channels_count * sample_format.bytes_per_sample();
+        assert_eq!(u32_at(bytes, 28), 48000 * block_align as u32);
+        assert_eq!(u16_at(bytes, 32), block_align);
+        assert_eq!(u16_at(bytes, 34), sample_format.bytes_per_sample() * 8);
+        assert_eq!(&bytes[36..40], b"data");
+        let data_length = u32_at(bytes, 40) as usize;
+        assert_eq!(
+            bytes.len(),
+            HEADER_LENGTH as usize + data_length + data_length % 2
+        );
+
+        let data = &bytes[HEADER_LENGTH as usize..HEADER_LENGTH as usize + data_length];
+        data.chunks_exact(sample_format.bytes_per_sample() as usize)
+            .map(|sample| match sample_format {
+                WavSampleFormat::Pcm16 => {
+                    i16::from_le_bytes(sample.try_into().unwrap()) as f32 / i16::MAX as f32
+                }
+                WavSampleFormat::Pcm24 => {
+                    let sample = i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) >> 8;
+                    sample as f32 / 8_388_607.0
+                }
+                WavSampleFormat::Float32 => f32::from_le_bytes(sample.try_into().unwrap()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        for sample_format in [
+            WavSampleFormat::Pcm16,
+            WavSampleFormat::Pcm24,
+            WavSampleFormat::Float32,
+        ] {
+            // 单声道3帧，24位时数据块长度为奇数。
+            let mut wav_writer =
+                WavWriter::new(Cursor::new(Vec::new()), sample_format, 1, 48000).unwrap();
+            wav_writer.write_channels(&[&[0.0, 0.5]]).unwrap();
+            wav_writer.write_channels(&[&[-1.0]]).unwrap();
+            let bytes = wav_writer.finish().unwrap().into_inner();
+            let samples = read_samples(&bytes, sample_format, 1);
+            assert_eq!(samples.len(), 3, "{sample_format:?}");
+            for (sample, expected) in samples.into_iter().zip([0.0, 0.5, -1.0]) {
+                assert!((sample - expected).abs() < 1e-4, "{sample_format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_multiple_channels() {
+        let mut wav_writer =
+            WavWriter::new(Cursor::new(Vec::new()), WavSampleFormat::Float32, 2, 48000).unwrap();
+        wav_writer
+            .write_channels(&[&[0.25, 0.5], &[-0.25]])
+            .unwrap();
+        let bytes = wav_writer.finish().unwrap().into_inner();
+        // 较短的通道补上静音。
+        assert_eq!(
+            read_samples(&bytes, WavSampleFormat::Float32, 2),
+            [0.25, -0.25, 0.5, 0.0]
+        );
+    }
+}